@@ -0,0 +1,35 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use log::{Level, Record};
+use redis_logger::{
+    CsvPubSubEncoder, DefaultPubSubEncoder, DefaultStreamEncoder, PubSubEncoder, SerializableLogRecord, StreamEncoder,
+};
+
+fn encoders(c: &mut Criterion) {
+    let key_values = [("user_id", 42), ("attempt", 3)];
+    let args = format_args!("Request to /api/orders failed after {} ms", 1250);
+    let record = Record::builder()
+        .level(Level::Warn)
+        .target("my_app::orders")
+        .module_path(Some("my_app::orders"))
+        .file(Some("src/orders.rs"))
+        .line(Some(128))
+        .key_values(&key_values)
+        .args(args)
+        .build();
+
+    c.bench_function("SerializableLogRecord::from", |b| {
+        b.iter(|| SerializableLogRecord::from(black_box(&record)));
+    });
+
+    let pubsub = DefaultPubSubEncoder::new();
+    c.bench_function("DefaultPubSubEncoder", |b| b.iter(|| pubsub.encode(black_box(&record))));
+
+    let stream = DefaultStreamEncoder::new();
+    c.bench_function("DefaultStreamEncoder", |b| b.iter(|| stream.encode(black_box(&record))));
+
+    let csv = CsvPubSubEncoder::new();
+    c.bench_function("CsvPubSubEncoder", |b| b.iter(|| csv.encode(black_box(&record))));
+}
+
+criterion_group!(benches, encoders);
+criterion_main!(benches);
@@ -0,0 +1,86 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use log::{LevelFilter, Log, Record};
+use redis::{ConnectionLike, RedisResult, Value};
+use redis_logger::{PubSubEncoder, RedisLogger, RedisLoggerConfigBuilder, StreamEncoder};
+
+/// A connection that answers every command without sending it, so only the cost of the logger is measured.
+struct NullConnection;
+
+impl ConnectionLike for NullConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        black_box(cmd);
+        Ok(Value::Int(1))
+    }
+
+    fn req_packed_commands(&mut self, cmd: &[u8], offset: usize, count: usize) -> RedisResult<Vec<Value>> {
+        black_box(cmd);
+        Ok(vec![Value::Int(1); offset + count])
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+
+    fn check_connection(&mut self) -> bool {
+        true
+    }
+
+    fn is_open(&self) -> bool {
+        true
+    }
+}
+
+struct MessageEncoder;
+
+impl PubSubEncoder for MessageEncoder {
+    fn encode(&self, record: &Record) -> Vec<u8> {
+        record.args().to_string().into_bytes()
+    }
+}
+
+/// Encodes a record into many fields, like a structured record with key-values flattened into the entry.
+struct ManyFieldsEncoder;
+
+impl StreamEncoder for ManyFieldsEncoder {
+    fn encode(&self, record: &Record) -> Vec<(String, Vec<u8>)> {
+        let mut fields = vec![
+            ("level".to_owned(), record.level().as_str().as_bytes().to_vec()),
+            ("target".to_owned(), record.target().as_bytes().to_vec()),
+            ("message".to_owned(), record.args().to_string().into_bytes()),
+        ];
+        fields.extend((0..20).map(|i| (format!("field{i}"), format!("value of field {i}").into_bytes())));
+        fields
+    }
+}
+
+fn logger(c: &mut Criterion) {
+    let args = format_args!("Request to /api/orders failed after {} ms", 1250);
+    let record = Record::builder()
+        .level(log::Level::Warn)
+        .target("my_app::orders")
+        .args(args)
+        .build();
+
+    let single = RedisLogger::new(
+        LevelFilter::Info,
+        RedisLoggerConfigBuilder::build_with_pubsub(NullConnection, vec!["logging".into()], MessageEncoder),
+    );
+    c.bench_function("single channel", |b| b.iter(|| single.log(black_box(&record))));
+
+    let channels = (0..4).map(|i| format!("logging{i}")).collect();
+    let multiple = RedisLogger::new(
+        LevelFilter::Info,
+        RedisLoggerConfigBuilder::build_with_pubsub(NullConnection, channels, MessageEncoder),
+    );
+    c.bench_function("four channels", |b| b.iter(|| multiple.log(black_box(&record))));
+
+    let stream = RedisLogger::new(
+        LevelFilter::Info,
+        RedisLoggerConfigBuilder::build_with_streams(NullConnection, vec!["logging".into()], ManyFieldsEncoder)
+            .with_stream_maxlen(10_000),
+    );
+    c.bench_function("stream with 23 fields", |b| b.iter(|| stream.log(black_box(&record))));
+}
+
+criterion_group!(benches, logger);
+criterion_main!(benches);
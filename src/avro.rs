@@ -0,0 +1,173 @@
+//! # Avro Module
+//!
+//! This module provides `AvroPubSubEncoder`, which encodes records as binary Apache Avro for schema registry pipelines.
+
+use std::{
+    convert::TryFrom,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use apache_avro::{types::Value, Schema};
+
+use super::{PubSubEncoder, Record};
+
+/// `AvroPubSubEncoder` encodes a record as a binary Apache Avro datum following the given record schema, optionally
+/// prefixed with the schema registry framing, i.e. a zero magic byte and the schema id as a big-endian `u32`, as
+/// expected by the Confluent serializers. To add the datum to a stream entry, wrap it in a `CompositeStreamEncoder`.
+///
+/// The schema's fields are filled by name: `level`, `message` (or `args`) and `target` with strings, `module_path` and
+/// `file` with optional strings, `line` with an optional `int` or `long`, and `timestamp` with the milliseconds since the
+/// Unix epoch as a `long`, e.g. with the logical type `timestamp-millis`. Any other field needs a default. As the module
+/// path, the file and the line may be unknown, their fields must allow `null`, e.g. `["null", "string"]`. The level may
+/// also be an enum with the symbols `ERROR`, `WARN`, `INFO`, `DEBUG` and `TRACE`.
+///
+/// ```rust
+/// # use redis_logger::AvroPubSubEncoder;
+/// let schema = apache_avro::Schema::parse_str(
+///     r#"{
+///         "type": "record",
+///         "name": "LogRecord",
+///         "fields": [
+///             { "name": "level", "type": "string" },
+///             { "name": "message", "type": "string" },
+///             { "name": "file", "type": ["null", "string"], "default": null }
+///         ]
+///     }"#,
+/// )
+/// .unwrap();
+/// let encoder = AvroPubSubEncoder::new(schema, Some(42)).unwrap();
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct AvroPubSubEncoder {
+    schema: Schema,
+    schema_id: Option<u32>,
+}
+
+impl AvroPubSubEncoder {
+    /// Creates an encoder for `schema`, framed with `schema_id` if given.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of encoding a sample record if the schema is not a record schema or doesn't fit the records, e.g.
+    /// if a field unknown to the encoder has no default or `file` doesn't allow `null`.
+    pub fn new(schema: Schema, schema_id: Option<u32>) -> Result<Self, apache_avro::Error> {
+        let encoder = Self { schema, schema_id };
+        // the records differ only in their values, so if both kinds of samples fit, every record fits
+        encoder.datum(&Record::builder().args(format_args!("sample")).build())?;
+        encoder.datum(
+            &Record::builder()
+                .args(format_args!("sample"))
+                .module_path(Some("sample"))
+                .file(Some("sample.rs"))
+                .line(Some(1))
+                .build(),
+        )?;
+        Ok(encoder)
+    }
+
+    /// Encodes the record as an Avro datum without framing.
+    fn datum(&self, record: &Record) -> Result<Vec<u8>, apache_avro::Error> {
+        let optional = |value: Option<&str>| value.map_or(Value::Null, |value| Value::String(value.to_owned()));
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let fields = vec![
+            ("level".to_owned(), Value::String(record.level().to_string())),
+            ("message".to_owned(), Value::String(record.args().to_string())),
+            ("args".to_owned(), Value::String(record.args().to_string())),
+            ("target".to_owned(), Value::String(record.target().to_owned())),
+            ("module_path".to_owned(), optional(record.module_path())),
+            ("file".to_owned(), optional(record.file())),
+            (
+                "line".to_owned(),
+                record
+                    .line()
+                    .map_or(Value::Null, |line| Value::Int(i32::try_from(line).unwrap_or(i32::MAX))),
+            ),
+            (
+                "timestamp".to_owned(),
+                Value::Long(i64::try_from(timestamp).unwrap_or(i64::MAX)),
+            ),
+        ];
+        // resolving picks the fields of the schema, converts the values to the schema's types and fills in defaults
+        let value = Value::Record(fields).resolve(&self.schema)?;
+        apache_avro::to_avro_datum(&self.schema, value)
+    }
+}
+
+impl PubSubEncoder for AvroPubSubEncoder {
+    fn encode(&self, record: &Record) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        if let Some(schema_id) = self.schema_id {
+            encoded.push(0);
+            encoded.extend_from_slice(&schema_id.to_be_bytes());
+        }
+        // `new` checked that records fit the schema, so encoding can't fail
+        encoded.extend(self.datum(record).unwrap_or_default());
+        encoded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "LogRecord",
+        "fields": [
+            { "name": "level", "type": { "type": "enum", "name": "Level", "symbols": ["ERROR", "WARN", "INFO", "DEBUG", "TRACE"] } },
+            { "name": "message", "type": "string" },
+            { "name": "target", "type": "string" },
+            { "name": "line", "type": ["null", "long"], "default": null },
+            { "name": "timestamp", "type": { "type": "long", "logicalType": "timestamp-millis" } },
+            { "name": "service", "type": "string", "default": "checkout" }
+        ]
+    }"#;
+
+    #[test]
+    fn test_avro_encoder() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let encoder = AvroPubSubEncoder::new(schema.clone(), Some(7)).unwrap();
+        let record = Record::builder()
+            .level(log::Level::Warn)
+            .target("my_target")
+            .args(format_args!("Disk almost full"))
+            .line(Some(42))
+            .build();
+
+        let encoded = encoder.encode(&record);
+        assert_eq!(encoded[..5], [0, 0, 0, 0, 7]);
+        let Value::Record(fields) = apache_avro::from_avro_datum(&schema, &mut &encoded[5..], None).unwrap() else {
+            panic!("not a record");
+        };
+
+        assert_eq!(fields[0], ("level".to_owned(), Value::Enum(1, "WARN".to_owned())));
+        assert_eq!(
+            fields[1],
+            ("message".to_owned(), Value::String("Disk almost full".to_owned()))
+        );
+        assert_eq!(fields[2], ("target".to_owned(), Value::String("my_target".to_owned())));
+        assert_eq!(fields[3], ("line".to_owned(), Value::Union(1, Box::new(Value::Long(42)))));
+        assert!(matches!(fields[4], (_, Value::TimestampMillis(timestamp)) if timestamp > 0));
+        assert_eq!(fields[5], ("service".to_owned(), Value::String("checkout".to_owned())));
+    }
+
+    #[test]
+    fn test_avro_encoder_without_framing() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let encoder = AvroPubSubEncoder::new(schema.clone(), None).unwrap();
+
+        let encoded = encoder.encode(&Record::builder().args(format_args!("Started")).build());
+        assert!(apache_avro::from_avro_datum(&schema, &mut &encoded[..], None).is_ok());
+    }
+
+    #[test]
+    fn test_avro_encoder_rejects_unfit_schema() {
+        let unknown_field = r#"{"type": "record", "name": "R", "fields": [{ "name": "host", "type": "string" }]}"#;
+        let not_nullable = r#"{"type": "record", "name": "R", "fields": [{ "name": "file", "type": "string" }]}"#;
+        for schema in [unknown_field, not_nullable, r#""string""#] {
+            let schema = Schema::parse_str(schema).unwrap();
+            assert!(AvroPubSubEncoder::new(schema, None).is_err());
+        }
+    }
+}
@@ -0,0 +1,485 @@
+//! # Composite Module
+//!
+//! This module provides `CompositeStreamEncoder`, which combines the output of several encoders into one stream entry,
+//! and `HybridStreamEncoder`, which adds a few scalar fields next to the fully encoded record. `FramedEncoder` prefixes
+//! the messages of another encoder with their length, and `LevelDispatchEncoder` chooses the encoder by level.
+
+use std::{
+    convert::TryFrom,
+    fmt,
+    ops::{Bound, RangeBounds},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::Level;
+
+#[cfg(feature = "compression")]
+use super::Compression;
+use super::{PubSubEncoder, Record, StreamEncoder};
+
+type FieldsEncoder = Box<dyn Fn(&Record) -> Vec<(String, Vec<u8>)> + Send + Sync>;
+
+/// The levels an encoder of `LevelDispatchEncoder` is used for.
+type LevelRange = (Bound<Level>, Bound<Level>);
+
+/// `CompositeStreamEncoder` combines several encoders into one `StreamEncoder`, so a single stream entry can hold
+/// different views of a record and every consumer reads the fields it needs, e.g. a lean `summary` field for dashboards
+/// and a `full` field with everything for investigations.
+///
+/// Pub/sub encoders are added as one named field holding their output, stream encoders contribute all of their fields.
+/// The fields appear in the order the encoders were added. Field names should be unique, as Redis keeps duplicate
+/// field names in an entry and consumers reading the entry into a map would only see one of them.
+///
+/// ```rust
+/// # use redis_logger::{CompositeStreamEncoder, PubSubEncoder};
+/// struct MessageEncoder;
+///
+/// impl PubSubEncoder for MessageEncoder {
+///     fn encode(&self, record: &log::Record) -> Vec<u8> {
+///         record.args().to_string().into_bytes()
+///     }
+/// }
+///
+/// let encoder = CompositeStreamEncoder::new().with_field("message", MessageEncoder);
+/// ```
+#[derive(Default)]
+#[non_exhaustive]
+pub struct CompositeStreamEncoder {
+    encoders: Vec<FieldsEncoder>,
+}
+
+impl CompositeStreamEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the field `name` holding the output of the pub/sub encoder.
+    #[must_use]
+    pub fn with_field<E>(mut self, name: impl Into<String>, encoder: E) -> Self
+    where
+        E: PubSubEncoder + 'static,
+    {
+        let name = name.into();
+        self.encoders
+            .push(Box::new(move |record| vec![(name.clone(), encoder.encode(record))]));
+        self
+    }
+
+    /// Adds all fields of the stream encoder.
+    #[must_use]
+    pub fn with_fields<E>(mut self, encoder: E) -> Self
+    where
+        E: StreamEncoder + 'static,
+    {
+        self.encoders.push(Box::new(move |record| encoder.encode(record)));
+        self
+    }
+}
+
+impl fmt::Debug for CompositeStreamEncoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompositeStreamEncoder")
+            .field("encoders", &self.encoders.len())
+            .finish()
+    }
+}
+
+impl StreamEncoder for CompositeStreamEncoder {
+    fn encode(&self, record: &Record) -> Vec<(String, Vec<u8>)> {
+        self.encoders.iter().flat_map(|encoder| encoder(record)).collect()
+    }
+}
+
+/// A scalar field of a record added by `HybridStreamEncoder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScalarField {
+    /// The field `level`, e.g. `INFO`.
+    Level,
+    /// The field `target`.
+    Target,
+    /// The field `timestamp` holding the time of encoding in milliseconds since the Unix epoch.
+    Timestamp,
+    /// The field `module_path`, empty if unknown.
+    ModulePath,
+    /// The field `file`, empty if unknown.
+    File,
+    /// The field `line`, empty if unknown.
+    Line,
+}
+
+impl ScalarField {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Level => "level",
+            Self::Target => "target",
+            Self::Timestamp => "timestamp",
+            Self::ModulePath => "module_path",
+            Self::File => "file",
+            Self::Line => "line",
+        }
+    }
+
+    fn value(self, record: &Record) -> Vec<u8> {
+        match self {
+            Self::Level => record.level().as_str().as_bytes().to_vec(),
+            Self::Target => record.target().as_bytes().to_vec(),
+            Self::Timestamp => {
+                let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+                u64::try_from(millis).unwrap_or(u64::MAX).to_string().into_bytes()
+            }
+            Self::ModulePath => record.module_path().unwrap_or_default().as_bytes().to_vec(),
+            Self::File => record.file().unwrap_or_default().as_bytes().to_vec(),
+            Self::Line => record.line().map(|line| line.to_string()).unwrap_or_default().into_bytes(),
+        }
+    }
+}
+
+/// `HybridStreamEncoder` splits every stream entry into a few scalar fields and one `payload` field holding the record
+/// encoded by a pub/sub encoder, e.g. `DefaultPubSubEncoder`. Consumers can filter entries on the scalar fields cheaply and
+/// decode the payload only for the entries they are interested in.
+///
+/// The scalar fields default to `level`, `target` and `timestamp` and come before the payload in the order given.
+/// With the feature flag `compression`, the payload can be compressed with `with_payload_compression` while the scalar
+/// fields stay plain text, so filtering stays cheap while the entries take less memory.
+///
+/// ```rust
+/// # use redis_logger::{HybridStreamEncoder, PubSubEncoder, ScalarField};
+/// struct MessageEncoder;
+///
+/// impl PubSubEncoder for MessageEncoder {
+///     fn encode(&self, record: &log::Record) -> Vec<u8> {
+///         record.args().to_string().into_bytes()
+///     }
+/// }
+///
+/// let encoder = HybridStreamEncoder::new(MessageEncoder).with_scalar_fields(vec![ScalarField::Level, ScalarField::File]);
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct HybridStreamEncoder<P: PubSubEncoder> {
+    scalar_fields: Vec<ScalarField>,
+    payload_encoder: P,
+    #[cfg(feature = "compression")]
+    payload_compression: Option<Compression>,
+}
+
+impl<P: PubSubEncoder> HybridStreamEncoder<P> {
+    /// Creates an encoder with the default scalar fields and `payload_encoder` encoding the `payload` field.
+    pub fn new(payload_encoder: P) -> Self {
+        Self {
+            scalar_fields: vec![ScalarField::Level, ScalarField::Target, ScalarField::Timestamp],
+            payload_encoder,
+            #[cfg(feature = "compression")]
+            payload_compression: None,
+        }
+    }
+
+    /// Sets the scalar fields added next to the payload.
+    #[must_use]
+    pub fn with_scalar_fields(mut self, scalar_fields: Vec<ScalarField>) -> Self {
+        self.scalar_fields = scalar_fields;
+        self
+    }
+
+    /// Compresses the payload with the given algorithm. Consumers must decompress the `payload` field before decoding it.
+    /// Defaults to `None`, i.e. no compression.
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    #[cfg(feature = "compression")]
+    #[must_use]
+    pub const fn with_payload_compression(mut self, payload_compression: Option<Compression>) -> Self {
+        self.payload_compression = payload_compression;
+        self
+    }
+
+    fn payload(&self, record: &Record) -> Vec<u8> {
+        let payload = self.payload_encoder.encode(record);
+        #[cfg(feature = "compression")]
+        if let Some(compression) = self.payload_compression {
+            return compression.compress(&payload);
+        }
+        payload
+    }
+}
+
+impl<P: PubSubEncoder> StreamEncoder for HybridStreamEncoder<P> {
+    fn encode(&self, record: &Record) -> Vec<(String, Vec<u8>)> {
+        self.scalar_fields
+            .iter()
+            .map(|field| (field.name().to_owned(), field.value(record)))
+            .chain(std::iter::once(("payload".to_owned(), self.payload(record))))
+            .collect()
+    }
+}
+
+/// `FramedEncoder` prepends the length of every message of another pub/sub encoder, for consumers that concatenate the
+/// messages into one buffer, e.g. by piping them to a file, and need to split them again.
+///
+/// Every frame is the length of the message as an unsigned 32-bit integer in big-endian byte order, followed by the
+/// message itself, so the message `hello` becomes the 9 bytes `00 00 00 05 68 65 6c 6c 6f`. Messages can't exceed
+/// 4 GiB, as Redis limits values to 512 MB.
+///
+/// ```rust
+/// # use redis_logger::{FramedEncoder, PubSubEncoder};
+/// struct MessageEncoder;
+///
+/// impl PubSubEncoder for MessageEncoder {
+///     fn encode(&self, record: &log::Record) -> Vec<u8> {
+///         record.args().to_string().into_bytes()
+///     }
+/// }
+///
+/// let encoder = FramedEncoder::new(MessageEncoder);
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct FramedEncoder<E: PubSubEncoder> {
+    encoder: E,
+}
+
+impl<E: PubSubEncoder> FramedEncoder<E> {
+    /// Creates an encoder framing the messages of `encoder`.
+    pub const fn new(encoder: E) -> Self {
+        Self { encoder }
+    }
+}
+
+impl<E: PubSubEncoder> PubSubEncoder for FramedEncoder<E> {
+    fn encode(&self, record: &Record) -> Vec<u8> {
+        let message = self.encoder.encode(record);
+        let mut frame = Vec::with_capacity(4 + message.len());
+        frame.extend(u32::try_from(message.len()).unwrap_or(u32::MAX).to_be_bytes());
+        frame.extend(message);
+        frame
+    }
+}
+
+/// `LevelDispatchEncoder` chooses the pub/sub encoder of a record by its level, e.g. verbose JSON for errors and a compact
+/// format for everything else, without a custom encoder. The levels of an encoder are given as a range, e.g.
+/// `Level::Error..=Level::Warn` or `..=Level::Warn` for warnings and errors, as `log` orders levels from the most severe
+/// `Error` to the least severe `Trace`. The first encoder whose range contains the level is used, or else the fallback.
+///
+/// ```rust
+/// # use log::Level;
+/// # use redis_logger::{LevelDispatchEncoder, PubSubEncoder};
+/// struct MessageEncoder;
+///
+/// impl PubSubEncoder for MessageEncoder {
+///     fn encode(&self, record: &log::Record) -> Vec<u8> {
+///         record.args().to_string().into_bytes()
+///     }
+/// }
+///
+/// struct VerboseEncoder;
+///
+/// impl PubSubEncoder for VerboseEncoder {
+///     fn encode(&self, record: &log::Record) -> Vec<u8> {
+///         format!("{} {}:{} {}", record.level(), record.file().unwrap_or("?"), record.line().unwrap_or(0), record.args())
+///             .into_bytes()
+///     }
+/// }
+///
+/// let encoder = LevelDispatchEncoder::new(MessageEncoder).with_levels(..=Level::Warn, VerboseEncoder);
+/// ```
+#[non_exhaustive]
+pub struct LevelDispatchEncoder {
+    encoders: Vec<(LevelRange, Box<dyn PubSubEncoder>)>,
+    fallback: Box<dyn PubSubEncoder>,
+}
+
+impl LevelDispatchEncoder {
+    /// Creates an encoder using `fallback` for all levels not added with `with_levels`.
+    pub fn new<E>(fallback: E) -> Self
+    where
+        E: PubSubEncoder + 'static,
+    {
+        Self {
+            encoders: Vec::new(),
+            fallback: Box::new(fallback),
+        }
+    }
+
+    /// Uses `encoder` for the records with a level in `levels`, unless an encoder added before covers the level as well.
+    #[must_use]
+    pub fn with_levels<E>(mut self, levels: impl RangeBounds<Level>, encoder: E) -> Self
+    where
+        E: PubSubEncoder + 'static,
+    {
+        let levels = (levels.start_bound().cloned(), levels.end_bound().cloned());
+        self.encoders.push((levels, Box::new(encoder)));
+        self
+    }
+}
+
+impl fmt::Debug for LevelDispatchEncoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LevelDispatchEncoder")
+            .field("levels", &self.encoders.iter().map(|(levels, _)| levels).collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PubSubEncoder for LevelDispatchEncoder {
+    fn encode(&self, record: &Record) -> Vec<u8> {
+        self.encoders
+            .iter()
+            .find(|(levels, _)| levels.contains(&record.level()))
+            .map_or(&self.fallback, |(_, encoder)| encoder)
+            .encode(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct LevelEncoder;
+
+    impl PubSubEncoder for LevelEncoder {
+        fn encode(&self, record: &Record) -> Vec<u8> {
+            record.level().as_str().as_bytes().to_vec()
+        }
+    }
+
+    struct LocationEncoder;
+
+    impl StreamEncoder for LocationEncoder {
+        fn encode(&self, record: &Record) -> Vec<(String, Vec<u8>)> {
+            vec![
+                ("file".to_owned(), record.file().unwrap_or_default().as_bytes().to_vec()),
+                ("line".to_owned(), record.line().unwrap_or_default().to_string().into_bytes()),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_composite_stream_encoder() {
+        let encoder = CompositeStreamEncoder::new()
+            .with_field("lean", LevelEncoder)
+            .with_fields(LocationEncoder);
+        let record = Record::builder()
+            .level(Level::Warn)
+            .args(format_args!("Disk almost full"))
+            .file(Some("my_file.rs"))
+            .line(Some(42))
+            .build();
+
+        assert_eq!(
+            encoder.encode(&record),
+            vec![
+                ("lean".to_owned(), b"WARN".to_vec()),
+                ("file".to_owned(), b"my_file.rs".to_vec()),
+                ("line".to_owned(), b"42".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hybrid_stream_encoder() {
+        struct MessageEncoder;
+
+        impl PubSubEncoder for MessageEncoder {
+            fn encode(&self, record: &Record) -> Vec<u8> {
+                format!("{{\"message\":\"{}\"}}", record.args()).into_bytes()
+            }
+        }
+
+        let record = Record::builder()
+            .level(Level::Warn)
+            .args(format_args!("Disk almost full"))
+            .target("my_target")
+            .line(Some(42))
+            .build();
+
+        let fields = HybridStreamEncoder::new(MessageEncoder).encode(&record);
+        let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["level", "target", "timestamp", "payload"]);
+        assert_eq!(fields[0].1, b"WARN");
+        assert_eq!(fields[1].1, b"my_target");
+        assert!(String::from_utf8_lossy(&fields[2].1).parse::<u64>().unwrap() > 0);
+        assert_eq!(fields[3].1, br#"{"message":"Disk almost full"}"#);
+
+        let encoder = HybridStreamEncoder::new(MessageEncoder).with_scalar_fields(vec![ScalarField::Line, ScalarField::File]);
+        assert_eq!(
+            encoder.encode(&record),
+            vec![
+                ("line".to_owned(), b"42".to_vec()),
+                ("file".to_owned(), b"".to_vec()),
+                ("payload".to_owned(), br#"{"message":"Disk almost full"}"#.to_vec()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_hybrid_stream_encoder_payload_compression() {
+        let record = Record::builder()
+            .level(Level::Warn)
+            .args(format_args!("Disk almost full"))
+            .target("my_target")
+            .build();
+
+        let fields = HybridStreamEncoder::new(LevelEncoder)
+            .with_scalar_fields(vec![ScalarField::Level, ScalarField::Target])
+            .with_payload_compression(Some(Compression::Zlib))
+            .encode(&record);
+
+        assert_eq!(fields[0], ("level".to_owned(), b"WARN".to_vec()));
+        assert_eq!(fields[1], ("target".to_owned(), b"my_target".to_vec()));
+        assert_eq!(fields[2].0, "payload");
+        assert_ne!(fields[2].1, b"WARN");
+        assert_eq!(miniz_oxide::inflate::decompress_to_vec_zlib(&fields[2].1).unwrap(), b"WARN");
+    }
+
+    #[test]
+    fn test_framed_encoder() {
+        struct MessageEncoder;
+
+        impl PubSubEncoder for MessageEncoder {
+            fn encode(&self, record: &Record) -> Vec<u8> {
+                record.args().to_string().into_bytes()
+            }
+        }
+
+        let encoder = FramedEncoder::new(MessageEncoder);
+        let frame = encoder.encode(&Record::builder().args(format_args!("hello")).build());
+        assert_eq!(frame, b"\x00\x00\x00\x05hello");
+
+        let message = "x".repeat(300);
+        let frame = encoder.encode(&Record::builder().args(format_args!("{message}")).build());
+        assert_eq!(frame[..4], [0, 0, 1, 44]);
+        assert_eq!(
+            u32::from_be_bytes(<[u8; 4]>::try_from(&frame[..4]).unwrap()) as usize,
+            frame.len() - 4
+        );
+        assert_eq!(frame[4..], *message.as_bytes());
+    }
+
+    #[test]
+    fn test_level_dispatch_encoder() {
+        struct TaggedEncoder(&'static str);
+
+        impl PubSubEncoder for TaggedEncoder {
+            fn encode(&self, record: &Record) -> Vec<u8> {
+                format!("{}:{}", self.0, record.level()).into_bytes()
+            }
+        }
+
+        let encoder = LevelDispatchEncoder::new(TaggedEncoder("fallback"))
+            .with_levels(Level::Error..=Level::Error, TaggedEncoder("a"))
+            .with_levels(..=Level::Info, TaggedEncoder("b"))
+            .with_levels(Level::Debug.., LevelEncoder);
+        let encode = |level| encoder.encode(&Record::builder().level(level).args(format_args!("Test")).build());
+
+        assert_eq!(encode(Level::Error), b"a:ERROR");
+        assert_eq!(encode(Level::Warn), b"b:WARN");
+        assert_eq!(encode(Level::Info), b"b:INFO");
+        assert_eq!(encode(Level::Trace), b"TRACE");
+
+        let encoder = LevelDispatchEncoder::new(TaggedEncoder("fallback")).with_levels(Level::Warn..Level::Debug, LevelEncoder);
+        let encode = |level| encoder.encode(&Record::builder().level(level).args(format_args!("Test")).build());
+        assert_eq!(encode(Level::Error), b"fallback:ERROR");
+        assert_eq!(encode(Level::Info), b"INFO");
+        assert_eq!(encode(Level::Debug), b"fallback:DEBUG");
+    }
+}
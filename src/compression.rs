@@ -0,0 +1,151 @@
+//! # Compression Module
+//!
+//! This module provides `Compression`, the algorithms encoders can compress fields with, and `CompressedPubSubEncoder`,
+//! which compresses the messages of another pub/sub encoder.
+
+use super::{PubSubEncoder, Record};
+
+/// A compression algorithm for encoded fields, e.g. the payload of `HybridStreamEncoder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// zlib (RFC 1950) at the default level, readable by virtually every platform, e.g. Python's `zlib.decompress`.
+    Zlib,
+    /// LZ4 block format prefixed with the uncompressed size as a little-endian `u32`, as written by
+    /// `lz4_flex::compress_prepend_size`. Faster than zlib but compresses less.
+    Lz4,
+    /// gzip (RFC 1952) at the default level, e.g. for `gunzip` or Python's `gzip.decompress`.
+    Gzip,
+}
+
+impl Compression {
+    /// Returns the conventional file extension of the algorithm, used as the suffix of compressed channels:
+    /// `zz` for zlib, `lz4` for LZ4 and `gz` for gzip.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Zlib => "zz",
+            Self::Lz4 => "lz4",
+            Self::Gzip => "gz",
+        }
+    }
+
+    pub(crate) fn compress(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Zlib => miniz_oxide::deflate::compress_to_vec_zlib(data, 6),
+            Self::Lz4 => lz4_flex::compress_prepend_size(data),
+            Self::Gzip => gzip(data),
+        }
+    }
+}
+
+/// Wraps the raw deflate stream in a minimal gzip header (no file name, unknown OS) and trailer.
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut gzip = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    gzip.extend(miniz_oxide::deflate::compress_to_vec(data, 6));
+    gzip.extend(crc32(data).to_le_bytes());
+    // the size modulo 2^32 as required by RFC 1952
+    gzip.extend((data.len() as u32).to_le_bytes());
+    gzip
+}
+
+/// The CRC-32 (ISO-HDLC) checksum used by gzip.
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+/// `CompressedPubSubEncoder` compresses the messages of another pub/sub encoder, for subscribers that decompress them.
+///
+/// By convention, channels carrying compressed messages are named with the extension of the algorithm as a suffix,
+/// e.g. `logging.gz` (see `Compression::extension`), so subscribers know how to decode them. To serve compressed and
+/// uncompressed messages at the same time, publish the uncompressed ones to `logging.raw` and add the compressed channel
+/// with `RedisLoggerConfig::with_compressed_channel`, which reuses the encoded message instead of encoding it twice.
+///
+/// ```rust
+/// # use redis_logger::{CompressedPubSubEncoder, Compression, PubSubEncoder};
+/// struct MessageEncoder;
+///
+/// impl PubSubEncoder for MessageEncoder {
+///     fn encode(&self, record: &log::Record) -> Vec<u8> {
+///         record.args().to_string().into_bytes()
+///     }
+/// }
+///
+/// let encoder = CompressedPubSubEncoder::new(MessageEncoder, Compression::Gzip);
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct CompressedPubSubEncoder<E: PubSubEncoder> {
+    encoder: E,
+    compression: Compression,
+}
+
+impl<E: PubSubEncoder> CompressedPubSubEncoder<E> {
+    /// Creates an encoder compressing the messages of `encoder` with `compression`.
+    pub const fn new(encoder: E, compression: Compression) -> Self {
+        Self { encoder, compression }
+    }
+}
+
+impl<E: PubSubEncoder> PubSubEncoder for CompressedPubSubEncoder<E> {
+    fn encode(&self, record: &Record) -> Vec<u8> {
+        self.compression.compress(&self.encoder.encode(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decompresses a gzip member as written by `gzip`, checking the trailer.
+    fn gunzip(gzip: &[u8]) -> Vec<u8> {
+        assert_eq!(gzip[..4], [0x1f, 0x8b, 8, 0]);
+        let (deflate, trailer) = gzip[10..].split_at(gzip.len() - 18);
+        let data = miniz_oxide::inflate::decompress_to_vec(deflate).unwrap();
+        assert_eq!(trailer[..4], crc32(&data).to_le_bytes());
+        assert_eq!(trailer[4..], (data.len() as u32).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_compression_round_trip() {
+        let data = b"Disk almost full, Disk almost full, Disk almost full".repeat(10);
+
+        let zlib = Compression::Zlib.compress(&data);
+        assert!(zlib.len() < data.len());
+        assert_eq!(miniz_oxide::inflate::decompress_to_vec_zlib(&zlib).unwrap(), data);
+
+        let lz4 = Compression::Lz4.compress(&data);
+        assert!(lz4.len() < data.len());
+        assert_eq!(lz4_flex::decompress_size_prepended(&lz4).unwrap(), data);
+
+        let gzip = Compression::Gzip.compress(&data);
+        assert!(gzip.len() < data.len());
+        assert_eq!(gunzip(&gzip), data);
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_compressed_pubsub_encoder() {
+        struct MessageEncoder;
+
+        impl PubSubEncoder for MessageEncoder {
+            fn encode(&self, record: &Record) -> Vec<u8> {
+                record.args().to_string().into_bytes()
+            }
+        }
+
+        let record = Record::builder().args(format_args!("Disk almost full")).build();
+        let encoded = CompressedPubSubEncoder::new(MessageEncoder, Compression::Gzip).encode(&record);
+
+        assert_eq!(gunzip(&encoded), b"Disk almost full");
+    }
+}
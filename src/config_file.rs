@@ -0,0 +1,282 @@
+//! # Config File Module
+//!
+//! This module provides `RedisLoggerFileConfig` to configure a logger with the default encoders from a TOML or JSON file.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use log::{LevelFilter, SetLoggerError};
+use redis::RedisError;
+use serde::{Deserialize, Deserializer};
+
+use super::{DefaultPubSubEncoder, DefaultStreamEncoder, Enrichment, RedisLogger, RedisLoggerConfig};
+
+/// `RedisLoggerFileConfig` is the content of a configuration file for a logger using `DefaultPubSubEncoder` and
+/// `DefaultStreamEncoder`, so operators can change the targets without recompiling. Custom encoders can't be configured
+/// in a file. Files ending in `.json` are parsed as JSON, all others as TOML.
+///
+/// ```toml
+/// url = "redis://127.0.0.1/"      # required
+/// level = "info"                  # defaults to "info"
+/// channels = ["logging"]          # at least one channel or stream is required
+/// streams = ["logging:stream"]
+///
+/// [encoder]                       # all options default to false
+/// timestamp = true                # see Enrichment::with_timestamp
+/// message_template = true         # see DefaultPubSubEncoder::with_message_template
+/// target_parts = false            # see DefaultPubSubEncoder::with_target_parts
+/// pretty = false                  # see DefaultPubSubEncoder::with_pretty, only applies to channels
+/// ```
+///
+/// Use `RedisLogger::from_file` to initialize the global logger from a file, or `into_config` to apply further settings
+/// with the `with_*` methods of `RedisLoggerConfig` first.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
+pub struct RedisLoggerFileConfig {
+    url: String,
+    #[serde(default = "default_level", deserialize_with = "deserialize_level")]
+    level: LevelFilter,
+    #[serde(default)]
+    channels: Vec<String>,
+    #[serde(default)]
+    streams: Vec<String>,
+    #[serde(default)]
+    encoder: EncoderFileConfig,
+}
+
+/// The options of the default encoders in the `encoder` table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EncoderFileConfig {
+    #[serde(default)]
+    timestamp: bool,
+    #[serde(default)]
+    message_template: bool,
+    #[serde(default)]
+    target_parts: bool,
+    #[serde(default)]
+    pretty: bool,
+}
+
+const fn default_level() -> LevelFilter {
+    LevelFilter::Info
+}
+
+fn deserialize_level<'de, D: Deserializer<'de>>(deserializer: D) -> Result<LevelFilter, D::Error> {
+    let level = String::deserialize(deserializer)?;
+    LevelFilter::from_str(&level).map_err(|_| serde::de::Error::custom(format!("invalid level '{level}'")))
+}
+
+impl RedisLoggerFileConfig {
+    /// Reads the configuration from the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, is not valid TOML or JSON, or configures neither channels nor streams.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|error| ConfigFileError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        let config: Self = if path.extension().is_some_and(|extension| extension == "json") {
+            serde_json::from_str(&content).map_err(|error| ConfigFileError::Parse(error.to_string()))?
+        } else {
+            toml::from_str(&content).map_err(|error| ConfigFileError::Parse(error.to_string()))?
+        };
+        if config.channels.is_empty() && config.streams.is_empty() {
+            return Err(ConfigFileError::NoTargets);
+        }
+        Ok(config)
+    }
+
+    /// Returns the configured level, which defaults to `info`.
+    pub const fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    /// Connects to Redis and returns the configuration for a `RedisLogger`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL is invalid or Redis is not reachable.
+    pub fn into_config(
+        self,
+    ) -> Result<RedisLoggerConfig<redis::Connection, DefaultPubSubEncoder, DefaultStreamEncoder>, ConfigFileError> {
+        let client = redis::Client::open(self.url.as_str())?;
+        let connection = client.get_connection()?;
+        let options = self.encoder;
+        let pubsub_encoder = DefaultPubSubEncoder::new()
+            .with_enrichment(Enrichment::new().with_timestamp(options.timestamp))
+            .with_message_template(options.message_template)
+            .with_target_parts(options.target_parts)
+            .with_pretty(options.pretty);
+        let stream_encoder = DefaultStreamEncoder::new()
+            .with_enrichment(Enrichment::new().with_timestamp(options.timestamp))
+            .with_message_template(options.message_template)
+            .with_target_parts(options.target_parts);
+        Ok(RedisLoggerConfig::new(
+            connection,
+            (!self.channels.is_empty()).then_some((self.channels, pubsub_encoder)),
+            (!self.streams.is_empty()).then_some((self.streams, stream_encoder)),
+        )
+        .with_connection_info(client.get_connection_info()))
+    }
+}
+
+impl RedisLogger<redis::Connection, DefaultPubSubEncoder, DefaultStreamEncoder> {
+    /// Reads the configuration file at `path` (see `RedisLoggerFileConfig`), connects to Redis and initializes the logger
+    /// as the global logger.
+    ///
+    /// ```no_run
+    /// redis_logger::RedisLogger::from_file("redis_logger.toml").unwrap();
+    /// log::info!("Configured from a file");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed, Redis is not reachable or a global logger has already been set.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<(), ConfigFileError> {
+        let config = RedisLoggerFileConfig::from_file(path)?;
+        let level = config.level();
+        Self::init(level, config.into_config()?)?;
+        Ok(())
+    }
+}
+
+/// An error returned by `RedisLoggerFileConfig` and `RedisLogger::from_file`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigFileError {
+    /// The file could not be read.
+    Io { path: PathBuf, error: io::Error },
+    /// The file is not valid TOML or JSON or doesn't match the expected structure.
+    Parse(String),
+    /// Neither channels nor streams are configured.
+    NoTargets,
+    /// The URL is invalid or the connection to Redis could not be established.
+    Redis(RedisError),
+    /// Another logger has already been set as the global logger.
+    SetLogger(SetLoggerError),
+}
+
+impl fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "Error reading the config file '{}': {error}", path.display()),
+            Self::Parse(error) => write!(f, "Error parsing the config file: {error}"),
+            Self::NoTargets => write!(f, "The config file configures neither channels nor streams"),
+            Self::Redis(error) => write!(f, "Error connecting to Redis: {error}"),
+            Self::SetLogger(error) => write!(f, "Error setting the global logger: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Redis(error) => Some(error),
+            Self::SetLogger(error) => Some(error),
+            Self::Parse(_) | Self::NoTargets => None,
+        }
+    }
+}
+
+impl From<RedisError> for ConfigFileError {
+    fn from(error: RedisError) -> Self {
+        Self::Redis(error)
+    }
+}
+
+impl From<SetLoggerError> for ConfigFileError {
+    fn from(error: SetLoggerError) -> Self {
+        Self::SetLogger(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("redis_logger_{}_{name}", std::process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_from_toml_file() {
+        let path = write_config(
+            "config.toml",
+            r#"
+url = "redis://127.0.0.1/"
+level = "debug"
+channels = ["logging"]
+streams = ["logging:stream"]
+
+[encoder]
+timestamp = true
+pretty = true
+"#,
+        );
+
+        let config = RedisLoggerFileConfig::from_file(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(config.url, "redis://127.0.0.1/");
+        assert_eq!(config.level(), LevelFilter::Debug);
+        assert_eq!(config.channels, ["logging"]);
+        assert_eq!(config.streams, ["logging:stream"]);
+        assert_eq!(
+            config.encoder,
+            EncoderFileConfig {
+                timestamp: true,
+                message_template: false,
+                target_parts: false,
+                pretty: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_json_file() {
+        let path = write_config("config.json", r#"{"url": "redis://127.0.0.1/", "streams": ["logging"]}"#);
+
+        let config = RedisLoggerFileConfig::from_file(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(config.level(), LevelFilter::Info);
+        assert!(config.channels.is_empty());
+        assert_eq!(config.streams, ["logging"]);
+        assert_eq!(config.encoder, EncoderFileConfig::default());
+    }
+
+    #[test]
+    fn test_from_invalid_file() {
+        let path = write_config(
+            "invalid.toml",
+            "url = \"redis://127.0.0.1/\"\nlevel = \"loud\"\nchannels = [\"logging\"]\n",
+        );
+        assert!(
+            matches!(RedisLoggerFileConfig::from_file(&path), Err(ConfigFileError::Parse(error)) if error.contains("invalid level 'loud'"))
+        );
+        fs::remove_file(path).unwrap();
+
+        let path = write_config("no_targets.toml", "url = \"redis://127.0.0.1/\"\n");
+        assert!(matches!(
+            RedisLoggerFileConfig::from_file(&path),
+            Err(ConfigFileError::NoTargets)
+        ));
+        fs::remove_file(path).unwrap();
+
+        assert!(matches!(
+            RedisLoggerFileConfig::from_file("does/not/exist.toml"),
+            Err(ConfigFileError::Io { .. })
+        ));
+    }
+}
@@ -0,0 +1,143 @@
+//! # CSV Module
+//!
+//! This module provides `CsvPubSubEncoder`, which encodes records as CSV lines for spreadsheet-friendly consumers.
+
+use std::fmt::Write;
+
+use super::{PubSubEncoder, Record};
+
+/// A column of the CSV lines written by `CsvPubSubEncoder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CsvColumn {
+    /// The level, e.g. `INFO`.
+    Level,
+    /// The target.
+    Target,
+    /// The rendered message.
+    Message,
+    /// The file, empty if unknown.
+    File,
+    /// The line, empty if unknown.
+    Line,
+    /// The module path, empty if unknown.
+    ModulePath,
+}
+
+/// `CsvPubSubEncoder` encodes a record as one CSV line (RFC 4180) without a trailing line break, by default with the
+/// columns `level,target,message,file,line`. Use `with_columns` to choose and order the columns.
+///
+/// Values containing a comma, a double quote or a line break are enclosed in double quotes, and double quotes within
+/// them are doubled, e.g. `say "hi", then leave` becomes `"say ""hi"", then leave"`. No header line is written.
+///
+/// ```rust
+/// # use redis_logger::{CsvColumn, CsvPubSubEncoder};
+/// let encoder = CsvPubSubEncoder::new().with_columns(vec![CsvColumn::Level, CsvColumn::Message]);
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct CsvPubSubEncoder {
+    columns: Vec<CsvColumn>,
+}
+
+impl CsvPubSubEncoder {
+    pub fn new() -> Self {
+        Self {
+            columns: vec![
+                CsvColumn::Level,
+                CsvColumn::Target,
+                CsvColumn::Message,
+                CsvColumn::File,
+                CsvColumn::Line,
+            ],
+        }
+    }
+
+    /// Sets the columns in the order they are written.
+    #[must_use]
+    pub fn with_columns(mut self, columns: Vec<CsvColumn>) -> Self {
+        self.columns = columns;
+        self
+    }
+}
+
+impl Default for CsvPubSubEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PubSubEncoder for CsvPubSubEncoder {
+    fn encode(&self, record: &Record) -> Vec<u8> {
+        let mut line = String::new();
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                line.push(',');
+            }
+            let value = match column {
+                CsvColumn::Level => record.level().as_str().to_owned(),
+                CsvColumn::Target => record.target().to_owned(),
+                CsvColumn::Message => record.args().to_string(),
+                CsvColumn::File => record.file().unwrap_or_default().to_owned(),
+                CsvColumn::Line => record.line().map(|line| line.to_string()).unwrap_or_default(),
+                CsvColumn::ModulePath => record.module_path().unwrap_or_default().to_owned(),
+            };
+            write_field(&mut line, &value);
+        }
+        line.into_bytes()
+    }
+}
+
+/// Appends the value, quoted and escaped if necessary.
+fn write_field(line: &mut String, value: &str) {
+    if value.contains([',', '"', '\n', '\r']) {
+        // writing to a String never fails
+        let _ = write!(line, "\"{}\"", value.replace('"', "\"\""));
+    } else {
+        line.push_str(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    fn encode(encoder: &CsvPubSubEncoder, args: std::fmt::Arguments<'_>) -> String {
+        let record = Record::builder()
+            .level(Level::Warn)
+            .args(args)
+            .target("my_target")
+            .file(Some("my_file.rs"))
+            .line(Some(42))
+            .build();
+        String::from_utf8(encoder.encode(&record)).unwrap()
+    }
+
+    #[test]
+    fn test_csv_pubsub_encoder() {
+        let encoder = CsvPubSubEncoder::new();
+        assert_eq!(
+            encode(&encoder, format_args!("Disk almost full")),
+            "WARN,my_target,Disk almost full,my_file.rs,42"
+        );
+        assert_eq!(
+            encode(&encoder, format_args!("Disk /dev/sda, 95% full")),
+            "WARN,my_target,\"Disk /dev/sda, 95% full\",my_file.rs,42"
+        );
+        assert_eq!(
+            encode(&encoder, format_args!("say \"hi\", then leave")),
+            "WARN,my_target,\"say \"\"hi\"\", then leave\",my_file.rs,42"
+        );
+        assert_eq!(
+            encode(&encoder, format_args!("first\nsecond")),
+            "WARN,my_target,\"first\nsecond\",my_file.rs,42"
+        );
+    }
+
+    #[test]
+    fn test_csv_pubsub_encoder_columns() {
+        let encoder = CsvPubSubEncoder::new().with_columns(vec![CsvColumn::Line, CsvColumn::ModulePath, CsvColumn::Message]);
+        assert_eq!(encode(&encoder, format_args!("\"quoted\"")), "42,,\"\"\"quoted\"\"\"");
+    }
+}
@@ -2,67 +2,731 @@
 //!
 //! This module provides default implementations for the `PubSubEncoder` and `StreamEncoder` traits.
 
-use serializable_log_record::SerializableLogRecord;
+use serde::Serialize;
+use serde_json::{Map, Value};
+/// The serializable form of a `log::Record` used by the default encoders, re-exported to use it in custom encoders or to
+/// benchmark the conversion with `SerializableLogRecord::from` in isolation.
+pub use serializable_log_record::SerializableLogRecord;
 
-use super::{PubSubEncoder, Record, StreamEncoder};
+use super::{
+    enrichment::millis_since_epoch,
+    key_values::{base64, flat_fields, nest_fields, KeyValues},
+    Enrichment, PubSubEncoder, Record, StreamEncoder,
+};
+
+/// A `SerializableLogRecord` together with its key-values and the fields added by the `Enrichment`.
+#[derive(Serialize)]
+struct EnrichedRecord {
+    #[serde(flatten)]
+    record: RecordFields,
+    #[serde(flatten)]
+    enrichment: Map<String, Value>,
+    #[serde(flatten)]
+    fields: Map<String, Value>,
+}
+
+/// The fields of the record itself, as they are or with the level mapped, the file and the line possibly combined into
+/// `location` and fields without a value possibly left out.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum RecordFields {
+    Separate(SerializableLogRecord),
+    Mapped(MappedRecord),
+}
+
+#[derive(Serialize)]
+struct MappedRecord {
+    level: Value,
+    args: String,
+    target: String,
+    /// `None` if left out, as opposed to `Some(None)`, which is written as `null`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module_path: Option<Option<String>>,
+    #[serde(flatten)]
+    location: Location,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Location {
+    Separate {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<Option<String>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<Option<u32>>,
+    },
+    Combined {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        location: Option<String>,
+    },
+}
+
+impl RecordFields {
+    fn new(record: &Record, options: EncoderOptions) -> Self {
+        let record = SerializableLogRecord::from(record);
+        let level_mapping = if options.level_both {
+            LevelMapping::LogNames
+        } else {
+            options.level_mapping
+        };
+        if !options.combined_location && level_mapping == LevelMapping::LogNames && !options.skip_none {
+            return Self::Separate(record);
+        }
+        let level = level_mapping.map(&record.level);
+        let location = if options.combined_location {
+            Location::Combined {
+                location: record.file.zip(record.line).map(|(file, line)| format!("{file}:{line}")),
+            }
+        } else {
+            Location::Separate {
+                file: nullable(record.file, options.skip_none),
+                line: nullable(record.line, options.skip_none),
+            }
+        };
+        Self::Mapped(MappedRecord {
+            level,
+            args: record.args,
+            target: record.target,
+            module_path: nullable(record.module_path, options.skip_none),
+            location,
+        })
+    }
+}
+
+/// Returns `None` to leave out a field without a value with `with_skip_none`, or else the value, which may be `null`.
+/// The fields are left out at serialization rather than removed afterwards, so they keep their order.
+fn nullable<T>(value: Option<T>, skip_none: bool) -> Option<Option<T>> {
+    (!skip_none || value.is_some()).then_some(value)
+}
+
+/// How the level of a record is written by `DefaultPubSubEncoder` and `DefaultStreamEncoder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum LevelMapping {
+    /// The name of the level as used by `log`, e.g. `WARN`. This is the default.
+    #[default]
+    LogNames,
+    /// The syslog severity (RFC 5424) as a number: `ERROR` is 3, `WARN` 4, `INFO` 6, and `DEBUG` and `TRACE` are 7, as
+    /// syslog has no lower severity. Use this for syslog-oriented systems, whose alerting thresholds compare severities.
+    Syslog,
+    /// The ordinal of the level in `log` as a number: `ERROR` is 1, `WARN` 2, `INFO` 3, `DEBUG` 4 and `TRACE` 5.
+    Numeric,
+}
+
+impl LevelMapping {
+    /// Maps the name of a level as written by `SerializableLogRecord`.
+    fn map(self, level: &str) -> Value {
+        let Ok(parsed) = level.parse::<log::Level>() else {
+            return Value::from(level);
+        };
+        match self {
+            Self::LogNames => Value::from(level),
+            Self::Syslog => Value::from(match parsed {
+                log::Level::Error => 3,
+                log::Level::Warn => 4,
+                log::Level::Info => 6,
+                log::Level::Debug | log::Level::Trace => 7,
+            }),
+            Self::Numeric => Value::from(parsed as usize),
+        }
+    }
+}
+
+impl EnrichedRecord {
+    fn new(record: &Record, enrichment: &Enrichment, options: EncoderOptions) -> Self {
+        let mut fields = Map::new();
+        if options.message_template {
+            if let Some(template) = record.args().as_str() {
+                fields.insert("template".to_owned(), Value::from(template));
+            }
+        }
+        if options.target_parts {
+            fields.insert("target_parts".to_owned(), record.target().split("::").collect());
+        }
+        if options.level_both {
+            fields.insert("level_num".to_owned(), options.level_num(record.level().as_str()));
+        }
+        let mut key_values = KeyValues::collect(record);
+        if options.skip_none {
+            key_values.fields.retain(|_, value| !value.is_null());
+        }
+        if options.nested_fields {
+            key_values.fields = nest_fields(key_values.fields);
+        }
+        if !key_values.fields.is_empty() {
+            let key_values = match options.flat_fields {
+                Some(separator) => Value::from(flat_fields(&key_values.fields, separator)),
+                None => Value::Object(key_values.fields),
+            };
+            fields.insert("fields".to_owned(), key_values);
+        }
+        if let Some(error_chain) = key_values.error_chain {
+            fields.insert("error_chain".to_owned(), Value::from(error_chain));
+        }
+        Self {
+            record: RecordFields::new(record, options),
+            enrichment: enrichment.fields(),
+            fields,
+        }
+    }
+}
+
+/// The optional fields shared by `DefaultPubSubEncoder` and `DefaultStreamEncoder`.
+#[derive(Debug, Clone, Copy)]
+struct EncoderOptions {
+    message_template: bool,
+    target_parts: bool,
+    combined_location: bool,
+    skip_none: bool,
+    level_mapping: LevelMapping,
+    level_both: bool,
+    nested_fields: bool,
+    flat_fields: Option<char>,
+}
+
+impl EncoderOptions {
+    const fn new() -> Self {
+        Self {
+            message_template: false,
+            target_parts: false,
+            combined_location: false,
+            skip_none: false,
+            level_mapping: LevelMapping::LogNames,
+            level_both: false,
+            nested_fields: false,
+            flat_fields: None,
+        }
+    }
+
+    /// Returns the number written as `level_num`: the ordinal with `LevelMapping::Numeric`, or else the syslog severity.
+    fn level_num(self, level: &str) -> Value {
+        match self.level_mapping {
+            LevelMapping::Numeric => LevelMapping::Numeric.map(level),
+            _ => LevelMapping::Syslog.map(level),
+        }
+    }
+}
 
 /// `DefaultPubSubEncoder` is a default implementation of the `PubSubEncoder` trait.
 /// It encodes a `log::Record` into a JSON object, where each field in the `Record` becomes a key-value pair in the JSON object.
 /// The JSON object is then converted into a byte vector.
 ///
+/// The structured key-values of a record (e.g. `log::info!(user = "alice"; "Logged in")`) are added as the object `fields`.
+/// If one of them holds an error captured with `:err` (e.g. `log::error!(error:err = e; "Request failed")`), the messages of
+/// the error and all of its sources are added as the array `error_chain`. Errors captured any other way (e.g. with `:%`) are
+/// only available as their rendered string, so their sources can't be walked.
+///
+/// The output is always valid UTF-8: messages are rendered through `fmt`, which only writes strings, and key-values holding
+/// bytes (e.g. captured with `serde_bytes`) are added as a string if they are valid UTF-8, or else as an object with their
+/// base64 encoding, e.g. `{"base64":"//4="}`. Implement your own encoder to send raw binary content.
+///
+/// With `with_message_template`, the message template is added as `template` next to the rendered message `args`.
+/// With `with_target_parts`, the target split on `::` is added as the array `target_parts`.
+/// With `with_combined_location`, the file and the line are combined into the field `location`, e.g. `src/main.rs:42`.
+/// With `with_skip_none`, fields and key-values without a value are left out instead of written as `null`.
+/// With `with_level_mapping`, the level is written differently, e.g. as a syslog severity, and with `with_level_both`
+/// as both its name and a number.
+/// With `with_nested_fields`, key-values with dotted keys like `http.method` are nested into objects in `fields`, and
+/// with `with_flat_fields`, they are written as one string like `user=alice id=5` instead.
+/// With `with_pretty`, the JSON object is pretty-printed for channels read by humans.
+///
 /// You can use these default encoders when you don't need to customize the encoding process.
 /// If you need to customize the encoding, you can implement the `PubSubEncoder` and `StreamEncoder` traits yourself.
 #[derive(Debug)]
 #[non_exhaustive]
-pub struct DefaultPubSubEncoder {}
+pub struct DefaultPubSubEncoder {
+    enrichment: Enrichment,
+    options: EncoderOptions,
+    pretty: bool,
+}
 
 impl DefaultPubSubEncoder {
     pub const fn new() -> Self {
-        Self {}
+        Self {
+            enrichment: Enrichment::new(),
+            options: EncoderOptions::new(),
+            pretty: false,
+        }
+    }
+
+    /// Sets the additional fields added to every record.
+    #[must_use]
+    pub fn with_enrichment(mut self, enrichment: Enrichment) -> Self {
+        self.enrichment = enrichment;
+        self
+    }
+
+    /// Pretty-prints the JSON object with indentation and line breaks, which is easier to read when tailing a channel
+    /// with `redis-cli SUBSCRIBE` during debugging. Defaults to `false`, i.e. compact JSON on a single line.
+    #[must_use]
+    pub const fn with_pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Adds the message template as the field `template` in addition to the rendered message. Defaults to `false`.
+    ///
+    /// `log` only exposes the template of messages without format arguments (see `fmt::Arguments::as_str`), so a
+    /// record like `log::info!("user {id} logged in")` has no template. Pass the variable parts as key-values instead,
+    /// e.g. `log::info!(id = 5; "user logged in")`, to get the constant template `user logged in` and `{"id":5}` in `fields`.
+    #[must_use]
+    pub const fn with_message_template(mut self, message_template: bool) -> Self {
+        self.options.message_template = message_template;
+        self
+    }
+
+    /// Adds the field `target_parts` holding the target split on `::` in addition to the full `target`, e.g.
+    /// `["myapp", "payments", "stripe"]` for `myapp::payments::stripe`, for hierarchical filtering downstream.
+    /// Defaults to `false`.
+    #[must_use]
+    pub const fn with_target_parts(mut self, target_parts: bool) -> Self {
+        self.options.target_parts = target_parts;
+        self
+    }
+
+    /// Replaces the fields `file` and `line` with the single field `location` holding both as `file:line`, e.g.
+    /// `src/main.rs:42`, as expected by many downstream tools. The field is left out if the file or the line is unknown.
+    /// Defaults to `false`.
+    #[must_use]
+    pub const fn with_combined_location(mut self, combined_location: bool) -> Self {
+        self.options.combined_location = combined_location;
+        self
+    }
+
+    /// Leaves out fields without a value instead of writing them as `null`: the fields `module_path`, `file` and `line`
+    /// if unknown, and key-values without a value, e.g. `log::info!(user = None::<&str>; "Logged in")`. Empty strings are
+    /// kept, as they are values. Defaults to `false`.
+    #[must_use]
+    pub const fn with_skip_none(mut self, skip_none: bool) -> Self {
+        self.options.skip_none = skip_none;
+        self
+    }
+
+    /// Sets how the level is written, e.g. as a syslog severity with `LevelMapping::Syslog`. Defaults to
+    /// `LevelMapping::LogNames`, i.e. the name of the level like `WARN`.
+    #[must_use]
+    pub const fn with_level_mapping(mut self, level_mapping: LevelMapping) -> Self {
+        self.options.level_mapping = level_mapping;
+        self
+    }
+
+    /// Writes the level both as its name in `level` and as a number in `level_num`, e.g. `"level":"INFO","level_num":6`,
+    /// for consumers expecting either. The number is the ordinal if the level mapping is `LevelMapping::Numeric`, and the
+    /// syslog severity otherwise. Defaults to `false`.
+    #[must_use]
+    pub const fn with_level_both(mut self, level_both: bool) -> Self {
+        self.options.level_both = level_both;
+        self
+    }
+
+    /// Nests key-values with dotted keys into objects in `fields`, e.g. `log::info!("http.method" = "GET", "http.path" = "/";
+    /// "Request")` into `{"http":{"method":"GET","path":"/"}}`, for systems that index nested fields. If a dotted key
+    /// conflicts with a value at a shorter path, e.g. `http.method` with a scalar `http`, it is kept flat with its full
+    /// name. Defaults to `false`.
+    #[must_use]
+    pub const fn with_nested_fields(mut self, nested_fields: bool) -> Self {
+        self.options.nested_fields = nested_fields;
+        self
+    }
+
+    /// Writes the key-values as one string in `fields` instead of an object, as `key=value` pairs joined by `separator`,
+    /// e.g. `user=alice id=5 query="SELECT 1"` with a space (logfmt), for consumers expecting a single string. Strings
+    /// are written as they are and other values as JSON. Keys and values that are empty or contain whitespace, `=`, `"`,
+    /// `\` or the separator are quoted and escaped like JSON strings. Defaults to an object.
+    #[must_use]
+    pub const fn with_flat_fields(mut self, separator: char) -> Self {
+        self.options.flat_fields = Some(separator);
+        self
+    }
+
+    /// Returns a JSON Schema (draft 2020-12) of the JSON objects this encoder writes with its current configuration, e.g.
+    /// for consumers validating the records they receive. Fields are only listed as required if every record has them,
+    /// and fields added by options or the `Enrichment` are only listed if enabled. Key-values are described as an object
+    /// of arbitrary values, as they differ from record to record.
+    pub fn json_schema(&self) -> Value {
+        let string = || serde_json::json!({ "type": "string" });
+        let strings = || serde_json::json!({ "type": "array", "items": { "type": "string" } });
+
+        let mut properties = Map::new();
+        let nullable = |schema: Value| match schema {
+            Value::Object(mut schema) if !self.options.skip_none => {
+                let ty = schema.remove("type").unwrap_or(Value::Null);
+                schema.insert("type".to_owned(), serde_json::json!([ty, "null"]));
+                Value::Object(schema)
+            }
+            schema => schema,
+        };
+        let mut required = vec!["level", "args", "target"];
+        if !self.options.skip_none {
+            required.push("module_path");
+        }
+        let names = ["ERROR", "WARN", "INFO", "DEBUG", "TRACE"];
+        if self.options.level_both {
+            properties.insert("level".to_owned(), serde_json::json!({ "enum": names }));
+            let numbers: Vec<Value> = names.iter().map(|level| self.options.level_num(level)).collect();
+            properties.insert("level_num".to_owned(), serde_json::json!({ "enum": numbers }));
+            required.push("level_num");
+        } else {
+            let levels: Vec<Value> = names.iter().map(|level| self.options.level_mapping.map(level)).collect();
+            properties.insert("level".to_owned(), serde_json::json!({ "enum": levels }));
+        }
+        properties.insert("args".to_owned(), string());
+        properties.insert("target".to_owned(), string());
+        properties.insert("module_path".to_owned(), nullable(string()));
+        if self.options.combined_location {
+            properties.insert("location".to_owned(), string());
+        } else {
+            properties.insert("file".to_owned(), nullable(string()));
+            properties.insert(
+                "line".to_owned(),
+                nullable(serde_json::json!({ "type": "integer", "minimum": 0 })),
+            );
+            if !self.options.skip_none {
+                required.extend(["file", "line"]);
+            }
+        }
+        if self.options.message_template {
+            properties.insert("template".to_owned(), string());
+        }
+        if self.options.target_parts {
+            properties.insert("target_parts".to_owned(), strings());
+            required.push("target_parts");
+        }
+        let fields = if self.options.flat_fields.is_some() {
+            "string"
+        } else {
+            "object"
+        };
+        properties.insert("fields".to_owned(), serde_json::json!({ "type": fields }));
+        properties.insert("error_chain".to_owned(), strings());
+        let enrichment = self.enrichment.schema_properties();
+        let mut required: Vec<String> = required.into_iter().map(str::to_owned).collect();
+        required.extend(enrichment.keys().filter(|field| *field != "correlation_id").cloned());
+        properties.extend(enrichment);
+        serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "Log record",
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        })
+    }
+}
+
+impl Default for DefaultPubSubEncoder {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl PubSubEncoder for DefaultPubSubEncoder {
     fn encode(&self, record: &Record) -> Vec<u8> {
-        let ser_record = SerializableLogRecord::from(record);
-        serde_json::to_string(&ser_record).unwrap().into_bytes()
+        let ser_record = EnrichedRecord::new(record, &self.enrichment, self.options);
+        if self.pretty {
+            serde_json::to_vec_pretty(&ser_record).unwrap()
+        } else {
+            serde_json::to_vec(&ser_record).unwrap()
+        }
     }
 }
 
 /// `DefaultStreamEncoder` is a default implementation of the `StreamEncoder` trait.
 /// It encodes a `log::Record` into a vector of tuples, where each tuple contains a field name from the `Record` and the
 /// corresponding value as a byte vector. If a field in the `Record` is `None`, the byte vector is empty.
+/// Other values than strings, like the line number, are written as JSON, e.g. `42`.
+///
+/// Key-values and error chains are added like in `DefaultPubSubEncoder`, as the JSON encoded fields `fields` and `error_chain`.
+/// The message template and the target parts are added as the fields `template` and `target_parts` if enabled with
+/// `with_message_template` and `with_target_parts`, and the file and the line are combined into `location` with
+/// `with_combined_location`. With `with_field_namespace`, the names of the record's own fields get a prefix, and with
+/// `with_skip_none`, fields without a value are left out instead of being empty. `with_level_mapping` and
+/// `with_level_both` change how the level is written, `with_nested_fields` nests dotted key-values in `fields`
+/// and `with_flat_fields` writes them as one string.
 ///
 /// You can use these default encoders when you don't need to customize the encoding process.
 /// If you need to customize the encoding, you can implement the `PubSubEncoder` and `StreamEncoder` traits yourself.
 #[derive(Debug)]
 #[non_exhaustive]
-pub struct DefaultStreamEncoder {}
+pub struct DefaultStreamEncoder {
+    enrichment: Enrichment,
+    options: EncoderOptions,
+    field_namespace: String,
+}
 
 impl DefaultStreamEncoder {
     pub const fn new() -> Self {
-        Self {}
+        Self {
+            enrichment: Enrichment::new(),
+            options: EncoderOptions::new(),
+            field_namespace: String::new(),
+        }
+    }
+
+    /// Prepends `prefix` to the names of the record's own fields, e.g. `rec_level` and `rec_args` with the prefix `rec_`,
+    /// while the fields added by the `Enrichment` keep their names. This keeps the record's fields apart from fields
+    /// added next to them, e.g. by another encoder in a `CompositeStreamEncoder`, so no value is lost to a name collision.
+    /// Defaults to no prefix.
+    #[must_use]
+    pub fn with_field_namespace(mut self, prefix: impl Into<String>) -> Self {
+        self.field_namespace = prefix.into();
+        self
+    }
+
+    /// Sets the additional fields added to every record.
+    #[must_use]
+    pub fn with_enrichment(mut self, enrichment: Enrichment) -> Self {
+        self.enrichment = enrichment;
+        self
+    }
+
+    /// Adds the message template as the field `template`, see `DefaultPubSubEncoder::with_message_template`.
+    #[must_use]
+    pub const fn with_message_template(mut self, message_template: bool) -> Self {
+        self.options.message_template = message_template;
+        self
+    }
+
+    /// Adds the target split on `::` as the field `target_parts`, see `DefaultPubSubEncoder::with_target_parts`.
+    #[must_use]
+    pub const fn with_target_parts(mut self, target_parts: bool) -> Self {
+        self.options.target_parts = target_parts;
+        self
+    }
+
+    /// Replaces the fields `file` and `line` with `location`, see `DefaultPubSubEncoder::with_combined_location`.
+    #[must_use]
+    pub const fn with_combined_location(mut self, combined_location: bool) -> Self {
+        self.options.combined_location = combined_location;
+        self
+    }
+
+    /// Leaves out fields without a value instead of writing them as empty, see `DefaultPubSubEncoder::with_skip_none`.
+    #[must_use]
+    pub const fn with_skip_none(mut self, skip_none: bool) -> Self {
+        self.options.skip_none = skip_none;
+        self
+    }
+
+    /// Sets how the level is written, see `DefaultPubSubEncoder::with_level_mapping`.
+    #[must_use]
+    pub const fn with_level_mapping(mut self, level_mapping: LevelMapping) -> Self {
+        self.options.level_mapping = level_mapping;
+        self
+    }
+
+    /// Writes the level both as its name and as a number, see `DefaultPubSubEncoder::with_level_both`.
+    #[must_use]
+    pub const fn with_level_both(mut self, level_both: bool) -> Self {
+        self.options.level_both = level_both;
+        self
+    }
+
+    /// Nests key-values with dotted keys into objects in `fields`, see `DefaultPubSubEncoder::with_nested_fields`.
+    #[must_use]
+    pub const fn with_nested_fields(mut self, nested_fields: bool) -> Self {
+        self.options.nested_fields = nested_fields;
+        self
+    }
+
+    /// Writes the key-values as one string in `fields`, see `DefaultPubSubEncoder::with_flat_fields`.
+    #[must_use]
+    pub const fn with_flat_fields(mut self, separator: char) -> Self {
+        self.options.flat_fields = Some(separator);
+        self
+    }
+}
+
+impl Default for DefaultStreamEncoder {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl StreamEncoder for DefaultStreamEncoder {
     fn encode(&self, record: &Record) -> Vec<(String, Vec<u8>)> {
-        let ser_record = SerializableLogRecord::from(record);
-        serde_json::to_value(&ser_record)
-            .unwrap_or_else(|_| serde_json::json!({}))
-            .as_object()
-            .unwrap()
-            .iter()
-            .map(|(k, v)| (k.clone(), v.as_str().unwrap_or("").to_owned().into_bytes()))
+        let mut ser_record = EnrichedRecord::new(record, &self.enrichment, self.options);
+        let enrichment = std::mem::take(&mut ser_record.enrichment);
+        let fields = match serde_json::to_value(&ser_record) {
+            Ok(Value::Object(fields)) => fields,
+            _ => Map::new(),
+        };
+        // the values are moved out of the maps instead of cloned, as this runs for every record
+        fields
+            .into_iter()
+            .map(|(k, v)| (format!("{}{k}", self.field_namespace), stream_value(v)))
+            .chain(enrichment.into_iter().map(|(k, v)| (k, stream_value(v))))
             .collect()
     }
 }
 
+/// Converts a JSON value into a stream field value. Strings are used as they are, `null` becomes empty.
+fn stream_value(value: Value) -> Vec<u8> {
+    match value {
+        Value::String(value) => value.into_bytes(),
+        Value::Null => Vec::new(),
+        value => value.to_string().into_bytes(),
+    }
+}
+
+/// `LokiStreamEncoder` is an implementation of the `StreamEncoder` trait for streams consumed by a Grafana Loki shipper.
+/// It splits a `log::Record` into two fields: `labels` holds a JSON object of the low-cardinality keys `level` and `target`,
+/// and `line` holds the rendered log message.
+///
+/// Loki indexes every distinct label combination as a separate stream, so labels must stay low-cardinality.
+/// This is why only the level and the target are used as labels while everything that varies per record goes into the line.
+/// If your targets are generated dynamically (e.g. contain ids), prefer a custom encoder that leaves them out of the labels.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct LokiStreamEncoder {}
+
+impl LokiStreamEncoder {
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for LokiStreamEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamEncoder for LokiStreamEncoder {
+    fn encode(&self, record: &Record) -> Vec<(String, Vec<u8>)> {
+        let labels = serde_json::json!({
+            "level": record.level().as_str(),
+            "target": record.target(),
+        });
+        vec![
+            ("labels".to_owned(), labels.to_string().into_bytes()),
+            ("line".to_owned(), record.args().to_string().into_bytes()),
+        ]
+    }
+}
+
+/// `ErrorTrackingStreamEncoder` is an implementation of the `StreamEncoder` trait for error-tracking consumers that group
+/// records by error. Every entry has the same fields: `level`, `message`, `error_type`, `error_message`, `file`, `line` and
+/// `timestamp`, the wall clock time of encoding in milliseconds since the Unix epoch.
+///
+/// `error_type` and `error_message` are taken from the key-value `error`, e.g. `log::error!(error:err = e; "Request failed")`.
+/// For an error, `error_type` is the leading type or variant name of its `Debug` output, e.g. `Os` for an `io::Error` from
+/// the operating system, and `error_message` its `Display` output. Other values only fill `error_message`. Fields without a
+/// value are empty.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ErrorTrackingStreamEncoder {}
+
+impl ErrorTrackingStreamEncoder {
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for ErrorTrackingStreamEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamEncoder for ErrorTrackingStreamEncoder {
+    fn encode(&self, record: &Record) -> Vec<(String, Vec<u8>)> {
+        let error = log::kv::Source::get(record.key_values(), log::kv::Key::from_str("error"));
+        let (error_type, error_message) = match error {
+            Some(value) => match value.to_borrowed_error() {
+                Some(error) => {
+                    let debug = format!("{error:?}");
+                    let end = debug
+                        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+                        .unwrap_or(debug.len());
+                    (debug[..end].to_owned(), error.to_string())
+                }
+                None => (String::new(), value.to_string()),
+            },
+            None => (String::new(), String::new()),
+        };
+        vec![
+            ("level".to_owned(), record.level().as_str().as_bytes().to_vec()),
+            ("message".to_owned(), record.args().to_string().into_bytes()),
+            ("error_type".to_owned(), error_type.into_bytes()),
+            ("error_message".to_owned(), error_message.into_bytes()),
+            ("file".to_owned(), record.file().unwrap_or_default().as_bytes().to_vec()),
+            (
+                "line".to_owned(),
+                record.line().map(|line| line.to_string()).unwrap_or_default().into_bytes(),
+            ),
+            (
+                "timestamp".to_owned(),
+                millis_since_epoch(std::time::SystemTime::now()).to_string().into_bytes(),
+            ),
+        ]
+    }
+}
+
+/// `EnvelopeEncoder` wraps the messages of another pub/sub encoder in a JSON envelope with a version and a type, e.g.
+/// `{"v":1,"type":"log","data":{...}}`, so consumers can handle messages of different encoders and versions uniformly.
+/// Set the version with `with_version` and the type with `with_type`; they default to `1` and `log`.
+///
+/// The message of the inner encoder is nested as `data` if it is valid JSON, like the output of `DefaultPubSubEncoder`.
+/// Any other message is added as an object with its base64 encoding, e.g. `{"base64":"//4="}`, as JSON can't hold raw bytes.
+///
+/// ```rust
+/// # use redis_logger::{DefaultPubSubEncoder, EnvelopeEncoder};
+/// let encoder = EnvelopeEncoder::new(DefaultPubSubEncoder::new()).with_version(2).with_type("audit");
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct EnvelopeEncoder<E: PubSubEncoder> {
+    encoder: E,
+    version: u64,
+    kind: String,
+}
+
+impl<E: PubSubEncoder> EnvelopeEncoder<E> {
+    /// Creates an encoder wrapping the messages of `encoder` in an envelope with the version `1` and the type `log`.
+    pub fn new(encoder: E) -> Self {
+        Self {
+            encoder,
+            version: 1,
+            kind: "log".to_owned(),
+        }
+    }
+
+    /// Sets the version written as `v`, to be increased when the layout of `data` changes. Defaults to `1`.
+    #[must_use]
+    pub const fn with_version(mut self, version: u64) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the type written as `type`. Defaults to `log`.
+    #[must_use]
+    pub fn with_type(mut self, kind: impl Into<String>) -> Self {
+        self.kind = kind.into();
+        self
+    }
+}
+
+impl<E: PubSubEncoder> PubSubEncoder for EnvelopeEncoder<E> {
+    fn encode(&self, record: &Record) -> Vec<u8> {
+        let message = self.encoder.encode(record);
+        let data =
+            serde_json::from_slice::<Value>(&message).unwrap_or_else(|_| serde_json::json!({ "base64": base64(&message) }));
+        let envelope = serde_json::json!({
+            "v": self.version,
+            "type": self.kind,
+            "data": data,
+        });
+        serde_json::to_vec(&envelope).unwrap()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use log::Level;
 
+    use crate::CompositeStreamEncoder;
+
     #[test]
     fn test_default_pubsub_encoder_encode() {
         let encoder = DefaultPubSubEncoder::new();
@@ -78,6 +742,16 @@ mod tests {
         let expected = r#"{"level":"INFO","args":"Test message","target":"my_target","module_path":"my_module","file":"my_file.rs","line":42}"#;
         let expected_bytes = expected.as_bytes().to_vec();
         assert_eq!(encoder.encode(&record), expected_bytes);
+
+        let expected = r#"{
+  "level": "INFO",
+  "args": "Test message",
+  "target": "my_target",
+  "module_path": "my_module",
+  "file": "my_file.rs",
+  "line": 42
+}"#;
+        assert_eq!(encoder.with_pretty(true).encode(&record), expected.as_bytes());
     }
 
     #[test]
@@ -102,5 +776,420 @@ mod tests {
         ];
 
         assert_eq!(encoder.encode(&record), expected);
+
+        let record = Record::builder()
+            .level(Level::Error)
+            .args(format_args!("Error message"))
+            .file(Some("my_file.rs"))
+            .line(Some(42))
+            .build();
+        let fields = encoder.encode(&record);
+        assert!(fields.contains(&("line".to_owned(), b"42".to_vec())));
+        let fields = DefaultStreamEncoder::new().with_combined_location(true).encode(&record);
+        assert!(fields.contains(&("location".to_owned(), b"my_file.rs:42".to_vec())));
+    }
+
+    #[test]
+    fn test_loki_stream_encoder_encode() {
+        let encoder = LokiStreamEncoder::new();
+        let record = Record::builder()
+            .level(Level::Warn)
+            .args(format_args!("Disk almost full"))
+            .target("my_target")
+            .file(Some("my_file.rs"))
+            .line(Some(42))
+            .build();
+
+        let expected = vec![
+            ("labels".to_owned(), br#"{"level":"WARN","target":"my_target"}"#.to_vec()),
+            ("line".to_owned(), b"Disk almost full".to_vec()),
+        ];
+
+        assert_eq!(encoder.encode(&record), expected);
+    }
+
+    #[test]
+    fn test_default_encoders_message_template() {
+        let key_values = [("id", log::kv::Value::from(5))];
+        let record = Record::builder()
+            .level(Level::Info)
+            .args(format_args!("user logged in"))
+            .target("my_target")
+            .key_values(&key_values)
+            .build();
+
+        let expected = r#"{"level":"INFO","args":"user logged in","target":"my_target","module_path":null,"file":null,"line":null,"fields":{"id":5},"template":"user logged in"}"#;
+        let encoder = DefaultPubSubEncoder::new().with_message_template(true);
+        assert_eq!(String::from_utf8(encoder.encode(&record)).unwrap(), expected);
+
+        let fields = DefaultStreamEncoder::new().with_message_template(true).encode(&record);
+        assert!(fields.contains(&("template".to_owned(), b"user logged in".to_vec())));
+        assert!(fields.contains(&("fields".to_owned(), br#"{"id":5}"#.to_vec())));
+
+        let id = 5;
+        let fields = DefaultStreamEncoder::new()
+            .with_message_template(true)
+            .encode(&Record::builder().args(format_args!("user {id} logged in")).build());
+        assert!(fields.contains(&("args".to_owned(), b"user 5 logged in".to_vec())));
+        assert!(!fields.iter().any(|(field, _)| field == "template"));
+    }
+
+    #[test]
+    fn test_default_encoders_target_parts() {
+        let record = Record::builder()
+            .args(format_args!("Charged"))
+            .target("myapp::payments::stripe")
+            .build();
+        let expected = r#"{"level":"INFO","args":"Charged","target":"myapp::payments::stripe","module_path":null,"file":null,"line":null,"target_parts":["myapp","payments","stripe"]}"#;
+        let encoder = DefaultPubSubEncoder::new().with_target_parts(true);
+        assert_eq!(String::from_utf8(encoder.encode(&record)).unwrap(), expected);
+
+        let record = Record::builder().args(format_args!("Started")).target("myapp").build();
+        let fields = DefaultStreamEncoder::new().with_target_parts(true).encode(&record);
+        assert!(fields.contains(&("target_parts".to_owned(), br#"["myapp"]"#.to_vec())));
+    }
+
+    #[test]
+    fn test_default_encoders_combined_location() {
+        let record = Record::builder()
+            .args(format_args!("Started"))
+            .target("my_target")
+            .file(Some("my_file.rs"))
+            .line(Some(42))
+            .build();
+        let expected = r#"{"level":"INFO","args":"Started","target":"my_target","module_path":null,"location":"my_file.rs:42"}"#;
+        let encoder = DefaultPubSubEncoder::new().with_combined_location(true);
+        assert_eq!(String::from_utf8(encoder.encode(&record)).unwrap(), expected);
+
+        let record = Record::builder()
+            .args(format_args!("Started"))
+            .target("my_target")
+            .file(Some("my_file.rs"))
+            .build();
+        let expected = r#"{"level":"INFO","args":"Started","target":"my_target","module_path":null}"#;
+        assert_eq!(String::from_utf8(encoder.encode(&record)).unwrap(), expected);
+
+        let fields = DefaultStreamEncoder::new().with_combined_location(true).encode(&record);
+        assert!(!fields
+            .iter()
+            .any(|(field, _)| ["file", "line", "location"].contains(&field.as_str())));
+    }
+
+    #[test]
+    fn test_default_stream_encoder_field_namespace() {
+        struct Shard;
+
+        impl PubSubEncoder for Shard {
+            fn encode(&self, _record: &Record) -> Vec<u8> {
+                b"eu-1".to_vec()
+            }
+        }
+
+        let encoder = CompositeStreamEncoder::new()
+            .with_fields(
+                DefaultStreamEncoder::new()
+                    .with_field_namespace("rec_")
+                    .with_enrichment(Enrichment::new().with_timestamp(true)),
+            )
+            .with_field("level", Shard);
+        let record = Record::builder()
+            .level(Level::Warn)
+            .args(format_args!("Disk almost full"))
+            .build();
+
+        let fields = encoder.encode(&record);
+        assert!(fields.contains(&("rec_level".to_owned(), b"WARN".to_vec())));
+        assert!(fields.contains(&("rec_args".to_owned(), b"Disk almost full".to_vec())));
+        assert!(fields.contains(&("level".to_owned(), b"eu-1".to_vec())));
+        assert!(fields.iter().any(|(field, _)| field == "timestamp"));
+        assert!(!fields.iter().any(|(field, _)| field == "args"));
+    }
+
+    #[test]
+    fn test_default_pubsub_encoder_encode_error_chain() {
+        #[derive(Debug)]
+        struct RequestError(std::io::Error);
+
+        impl std::fmt::Display for RequestError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("request failed")
+            }
+        }
+
+        impl std::error::Error for RequestError {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        let error = RequestError(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "connection reset"));
+        let key_values = [("error", log::kv::Value::from_dyn_error(&error))];
+        let record = Record::builder()
+            .level(Level::Error)
+            .args(format_args!("Request failed"))
+            .target("my_target")
+            .key_values(&key_values)
+            .build();
+
+        let expected = r#"{"level":"ERROR","args":"Request failed","target":"my_target","module_path":null,"file":null,"line":null,"error_chain":["request failed","connection reset"],"fields":{"error":"request failed"}}"#;
+        assert_eq!(
+            String::from_utf8(DefaultPubSubEncoder::new().encode(&record)).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_error_tracking_stream_encoder_encode() {
+        #[derive(Debug)]
+        enum PaymentError {
+            Declined { code: u32 },
+        }
+
+        impl std::fmt::Display for PaymentError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    Self::Declined { code } => write!(f, "card declined ({code})"),
+                }
+            }
+        }
+
+        impl std::error::Error for PaymentError {}
+
+        let encoder = ErrorTrackingStreamEncoder::new();
+        let error = PaymentError::Declined { code: 51 };
+        let key_values = [("error", log::kv::Value::from_dyn_error(&error))];
+        let record = Record::builder()
+            .level(Level::Error)
+            .args(format_args!("Payment failed"))
+            .file(Some("my_file.rs"))
+            .line(Some(42))
+            .key_values(&key_values)
+            .build();
+        let mut fields = encoder.encode(&record);
+        let (field, timestamp) = fields.pop().unwrap();
+        assert_eq!(field, "timestamp");
+        assert!(String::from_utf8(timestamp).unwrap().parse::<u64>().unwrap() > 0);
+        assert_eq!(
+            fields,
+            vec![
+                ("level".to_owned(), b"ERROR".to_vec()),
+                ("message".to_owned(), b"Payment failed".to_vec()),
+                ("error_type".to_owned(), b"Declined".to_vec()),
+                ("error_message".to_owned(), b"card declined (51)".to_vec()),
+                ("file".to_owned(), b"my_file.rs".to_vec()),
+                ("line".to_owned(), b"42".to_vec()),
+            ]
+        );
+
+        let record = Record::builder()
+            .level(Level::Info)
+            .args(format_args!("Payment received"))
+            .build();
+        let mut fields = encoder.encode(&record);
+        fields.pop();
+        assert_eq!(
+            fields,
+            vec![
+                ("level".to_owned(), b"INFO".to_vec()),
+                ("message".to_owned(), b"Payment received".to_vec()),
+                ("error_type".to_owned(), b"".to_vec()),
+                ("error_message".to_owned(), b"".to_vec()),
+                ("file".to_owned(), b"".to_vec()),
+                ("line".to_owned(), b"".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_default_pubsub_encoder_json_schema() {
+        let schema = DefaultPubSubEncoder::new().json_schema();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["line"]["type"], serde_json::json!(["integer", "null"]));
+        assert_eq!(
+            schema["required"],
+            serde_json::json!(["level", "args", "target", "module_path", "file", "line"])
+        );
+        assert!(schema["properties"].get("timestamp").is_none());
+
+        let schema = DefaultPubSubEncoder::new()
+            .with_enrichment(Enrichment::new().with_timestamp(true))
+            .with_combined_location(true)
+            .json_schema();
+        assert_eq!(schema["properties"]["timestamp"]["type"], "integer");
+        assert!(schema["properties"].get("file").is_none());
+        assert_eq!(
+            schema["required"],
+            serde_json::json!(["level", "args", "target", "module_path", "timestamp"])
+        );
+    }
+
+    #[test]
+    fn test_default_encoders_skip_none() {
+        let key_values: [(&str, log::kv::Value); 2] = [("user", log::kv::Value::null()), ("attempt", 3.into())];
+        let record = Record::builder()
+            .level(Level::Info)
+            .args(format_args!("Logged in"))
+            .target("my_target")
+            .key_values(&key_values)
+            .build();
+
+        let expected = r#"{"level":"INFO","args":"Logged in","target":"my_target","module_path":null,"file":null,"line":null,"fields":{"attempt":3,"user":null}}"#;
+        assert_eq!(DefaultPubSubEncoder::new().encode(&record), expected.as_bytes());
+        let expected = r#"{"level":"INFO","args":"Logged in","target":"my_target","fields":{"attempt":3}}"#;
+        assert_eq!(
+            DefaultPubSubEncoder::new().with_skip_none(true).encode(&record),
+            expected.as_bytes()
+        );
+        let located = Record::builder()
+            .level(Level::Warn)
+            .args(format_args!("Disk almost full"))
+            .target("my_target")
+            .file(Some("my_file.rs"))
+            .line(Some(42))
+            .build();
+        let expected = r#"{"level":4,"args":"Disk almost full","target":"my_target","file":"my_file.rs","line":42}"#;
+        let encoder = DefaultPubSubEncoder::new()
+            .with_skip_none(true)
+            .with_level_mapping(LevelMapping::Syslog);
+        assert_eq!(encoder.encode(&located), expected.as_bytes());
+
+        let fields: Vec<String> = DefaultStreamEncoder::new()
+            .with_skip_none(true)
+            .encode(&record)
+            .into_iter()
+            .map(|(field, _)| field)
+            .collect();
+        assert_eq!(fields, ["args", "fields", "level", "target"]);
+
+        let schema = DefaultPubSubEncoder::new().with_skip_none(true).json_schema();
+        assert_eq!(schema["properties"]["file"]["type"], "string");
+        assert_eq!(schema["required"], serde_json::json!(["level", "args", "target"]));
+    }
+
+    #[test]
+    fn test_envelope_encoder_encode() {
+        struct MessageEncoder;
+
+        impl PubSubEncoder for MessageEncoder {
+            fn encode(&self, record: &Record) -> Vec<u8> {
+                record.args().to_string().into_bytes()
+            }
+        }
+
+        let record = Record::builder()
+            .level(Level::Info)
+            .args(format_args!("Test message"))
+            .target("my_target")
+            .build();
+
+        let encoded = EnvelopeEncoder::new(DefaultPubSubEncoder::new().with_skip_none(true)).encode(&record);
+        let expected = r#"{"data":{"args":"Test message","level":"INFO","target":"my_target"},"type":"log","v":1}"#;
+        assert_eq!(String::from_utf8(encoded).unwrap(), expected);
+
+        let encoded = EnvelopeEncoder::new(MessageEncoder)
+            .with_version(2)
+            .with_type("audit")
+            .encode(&record);
+        let expected = r#"{"data":{"base64":"VGVzdCBtZXNzYWdl"},"type":"audit","v":2}"#;
+        assert_eq!(String::from_utf8(encoded).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_default_encoders_level_mapping() {
+        let record = Record::builder()
+            .level(Level::Warn)
+            .args(format_args!("Disk almost full"))
+            .file(Some("my_file.rs"))
+            .line(Some(42))
+            .build();
+        let level = |mapping| {
+            let fields = DefaultStreamEncoder::new().with_level_mapping(mapping).encode(&record);
+            fields.into_iter().find(|(field, _)| field == "level").unwrap().1
+        };
+        assert_eq!(level(LevelMapping::LogNames), b"WARN");
+        assert_eq!(level(LevelMapping::Syslog), b"4");
+        assert_eq!(level(LevelMapping::Numeric), b"2");
+
+        let encoded = DefaultPubSubEncoder::new()
+            .with_level_mapping(LevelMapping::Syslog)
+            .encode(&record);
+        let expected = r#"{"level":4,"args":"Disk almost full","target":"","module_path":null,"file":"my_file.rs","line":42}"#;
+        assert_eq!(String::from_utf8(encoded).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_default_encoders_level_both() {
+        let encode = |encoder: &DefaultPubSubEncoder, level| {
+            let record = Record::builder().level(level).args(format_args!("Test message")).build();
+            serde_json::from_slice::<Value>(&encoder.encode(&record)).unwrap()
+        };
+        let encoder = DefaultPubSubEncoder::new().with_level_both(true);
+        let record = encode(&encoder, Level::Info);
+        assert_eq!(
+            (&record["level"], &record["level_num"]),
+            (&Value::from("INFO"), &Value::from(6))
+        );
+        let record = encode(&encoder, Level::Error);
+        assert_eq!(
+            (&record["level"], &record["level_num"]),
+            (&Value::from("ERROR"), &Value::from(3))
+        );
+
+        let encoder = DefaultPubSubEncoder::new()
+            .with_level_mapping(LevelMapping::Numeric)
+            .with_level_both(true);
+        let record = encode(&encoder, Level::Warn);
+        assert_eq!(
+            (&record["level"], &record["level_num"]),
+            (&Value::from("WARN"), &Value::from(2))
+        );
+        assert_eq!(
+            encoder.json_schema()["properties"]["level_num"]["enum"],
+            serde_json::json!([1, 2, 3, 4, 5])
+        );
+    }
+
+    #[test]
+    fn test_default_encoders_flat_fields() {
+        let key_values = [
+            ("user", log::kv::Value::from("alice")),
+            ("query", log::kv::Value::from("SELECT \"name\" FROM users")),
+        ];
+        let record = Record::builder().args(format_args!("Query")).key_values(&key_values).build();
+        let expected = r#"query="SELECT \"name\" FROM users" user=alice"#;
+
+        let encoder = DefaultPubSubEncoder::new().with_flat_fields(' ');
+        let encoded = encoder.encode(&record);
+        assert_eq!(serde_json::from_slice::<Value>(&encoded).unwrap()["fields"], expected);
+        assert_eq!(encoder.json_schema()["properties"]["fields"]["type"], "string");
+
+        let encoded = DefaultStreamEncoder::new().with_flat_fields(' ').encode(&record);
+        assert!(encoded.contains(&("fields".to_owned(), expected.as_bytes().to_vec())));
+    }
+
+    #[test]
+    fn test_default_encoders_nested_fields() {
+        let key_values = [
+            ("http.method", log::kv::Value::from("GET")),
+            ("http.path", log::kv::Value::from("/users")),
+        ];
+        let record = Record::builder()
+            .args(format_args!("Request"))
+            .key_values(&key_values)
+            .build();
+        let expected = serde_json::json!({ "http": { "method": "GET", "path": "/users" } });
+
+        let encoded = DefaultPubSubEncoder::new().with_nested_fields(true).encode(&record);
+        assert_eq!(serde_json::from_slice::<Value>(&encoded).unwrap()["fields"], expected);
+
+        let encoded = DefaultStreamEncoder::new().with_nested_fields(true).encode(&record);
+        let (_, fields) = encoded.iter().find(|(name, _)| name == "fields").unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(fields).unwrap(), expected);
+
+        let encoded = DefaultPubSubEncoder::new().encode(&record);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&encoded).unwrap()["fields"]["http.method"],
+            "GET"
+        );
     }
 }
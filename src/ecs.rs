@@ -0,0 +1,117 @@
+//! # ECS Module
+//!
+//! This module provides a `PubSubEncoder` producing JSON that follows the Elastic Common Schema (ECS).
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+
+use super::{PubSubEncoder, Record};
+
+/// The ECS version the encoded records conform to.
+const ECS_VERSION: &str = "8.11";
+
+/// `EcsPubSubEncoder` is an implementation of the `PubSubEncoder` trait producing Elastic Common Schema (ECS) compatible JSON,
+/// so records can be ingested into Elasticsearch without a transform step.
+///
+/// A record is encoded with the fields `@timestamp` (the time of encoding), `log.level`, `log.logger` (the target),
+/// `log.origin.file.name`, `log.origin.file.line`, `log.origin.function` (the module path), `message` and `ecs.version`.
+/// As required by ECS, dotted field names are encoded as nested objects. Fields without a value are left out.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct EcsPubSubEncoder {}
+
+impl EcsPubSubEncoder {
+    pub const fn new() -> Self {
+        Self {}
+    }
+
+    fn encode_at(record: &Record, timestamp: DateTime<Utc>) -> Value {
+        let mut file = Map::new();
+        if let Some(name) = record.file() {
+            file.insert("name".to_owned(), Value::from(name));
+        }
+        if let Some(line) = record.line() {
+            file.insert("line".to_owned(), Value::from(line));
+        }
+        let mut origin = Map::new();
+        if !file.is_empty() {
+            origin.insert("file".to_owned(), Value::Object(file));
+        }
+        if let Some(module_path) = record.module_path() {
+            origin.insert("function".to_owned(), Value::from(module_path));
+        }
+        let mut log = Map::new();
+        log.insert("level".to_owned(), Value::from(record.level().as_str().to_lowercase()));
+        log.insert("logger".to_owned(), Value::from(record.target()));
+        if !origin.is_empty() {
+            log.insert("origin".to_owned(), Value::Object(origin));
+        }
+
+        json!({
+            "@timestamp": timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "log": log,
+            "message": record.args().to_string(),
+            "ecs": { "version": ECS_VERSION },
+        })
+    }
+}
+
+impl Default for EcsPubSubEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PubSubEncoder for EcsPubSubEncoder {
+    fn encode(&self, record: &Record) -> Vec<u8> {
+        Self::encode_at(record, Utc::now()).to_string().into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use log::Level;
+
+    #[test]
+    fn test_ecs_pubsub_encoder_encode() {
+        let record = Record::builder()
+            .level(Level::Info)
+            .args(format_args!("Test message"))
+            .target("my_target")
+            .module_path(Some("my_module"))
+            .file(Some("my_file.rs"))
+            .line(Some(42))
+            .build();
+        let timestamp = Utc.with_ymd_and_hms(2024, 6, 1, 12, 30, 0).unwrap();
+
+        let expected = r#"{"@timestamp":"2024-06-01T12:30:00.000Z","ecs":{"version":"8.11"},"log":{"level":"info","logger":"my_target","origin":{"file":{"line":42,"name":"my_file.rs"},"function":"my_module"}},"message":"Test message"}"#;
+        assert_eq!(EcsPubSubEncoder::encode_at(&record, timestamp).to_string(), expected);
+    }
+
+    #[test]
+    fn test_ecs_pubsub_encoder_without_origin() {
+        let record = Record::builder()
+            .level(Level::Error)
+            .args(format_args!("Failed"))
+            .target("my_target")
+            .build();
+        let timestamp = Utc.with_ymd_and_hms(2024, 6, 1, 12, 30, 0).unwrap();
+
+        let expected = r#"{"@timestamp":"2024-06-01T12:30:00.000Z","ecs":{"version":"8.11"},"log":{"level":"error","logger":"my_target"},"message":"Failed"}"#;
+        assert_eq!(EcsPubSubEncoder::encode_at(&record, timestamp).to_string(), expected);
+    }
+
+    #[test]
+    fn test_ecs_pubsub_encoder_encode_is_json() {
+        let record = Record::builder()
+            .level(Level::Warn)
+            .args(format_args!("Test message"))
+            .build();
+
+        let encoded: Value = serde_json::from_slice(&EcsPubSubEncoder::new().encode(&record)).unwrap();
+        assert_eq!(encoded["log"]["level"], "warn");
+        assert!(encoded["@timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+}
@@ -0,0 +1,301 @@
+//! # Enrichment Module
+//!
+//! This module provides `Enrichment`, a set of additional fields the default encoders add to every record.
+
+use std::{
+    convert::TryFrom,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Map, Value};
+
+/// `Enrichment` configures additional fields that `DefaultPubSubEncoder` and `DefaultStreamEncoder` add to every record.
+/// Pass it to the encoders with `with_enrichment`. By default, no fields are added.
+///
+/// ```rust
+/// # use redis_logger::{DefaultPubSubEncoder, Enrichment};
+/// let encoder = DefaultPubSubEncoder::new().with_enrichment(Enrichment::new().with_timestamp(true));
+/// ```
+#[derive(Default)]
+#[non_exhaustive]
+pub struct Enrichment {
+    timestamp: bool,
+    monotonic: Option<Monotonic>,
+    uptime: Option<Uptime>,
+    build: Option<Value>,
+    instance: Option<String>,
+    correlation_id: Option<CorrelationIdSource>,
+    clock: Option<Box<dyn Fn() -> SystemTime + Send + Sync>>,
+}
+
+type CorrelationIdSource = Box<dyn Fn() -> Option<String> + Send + Sync>;
+
+impl fmt::Debug for Enrichment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Enrichment")
+            .field("timestamp", &self.timestamp)
+            .field("monotonic", &self.monotonic)
+            .field("uptime", &self.uptime)
+            .field("build", &self.build)
+            .field("instance", &self.instance)
+            .field("correlation_id", &self.correlation_id.is_some())
+            .field("clock", &self.clock.is_some())
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+struct Monotonic {
+    start: Instant,
+    last: AtomicU64,
+}
+
+impl Monotonic {
+    /// Returns the nanoseconds since `start`, but at least one more than the previously returned value.
+    fn next(&self) -> u64 {
+        let elapsed = u64::try_from(self.start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        let previous = self
+            .last
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| Some(elapsed.max(last + 1)))
+            .unwrap_or_default();
+        elapsed.max(previous + 1)
+    }
+}
+
+/// The point in time the uptime is measured from.
+#[derive(Debug)]
+struct Uptime {
+    start: Instant,
+    start_time: u64,
+}
+
+impl Enrichment {
+    pub const fn new() -> Self {
+        Self {
+            timestamp: false,
+            monotonic: None,
+            uptime: None,
+            build: None,
+            instance: None,
+            correlation_id: None,
+            clock: None,
+        }
+    }
+
+    /// Adds the field `timestamp` holding the wall clock time of encoding in milliseconds since the Unix epoch.
+    /// The time is taken from the clock set with `with_clock`, which defaults to `SystemTime::now`.
+    #[must_use]
+    pub const fn with_timestamp(mut self, timestamp: bool) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Sets the clock used for the `timestamp` field instead of `SystemTime::now`, e.g. a fixed clock in tests or the
+    /// simulation time of an application that manages its own clock.
+    #[must_use]
+    pub fn with_clock<F>(mut self, clock: F) -> Self
+    where
+        F: Fn() -> SystemTime + Send + Sync + 'static,
+    {
+        self.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// Adds the field `monotonic_ns` holding the nanoseconds since this `Enrichment` was created, taken from a monotonic clock.
+    ///
+    /// The value strictly increases from record to record, even for records encoded within the same nanosecond or if the
+    /// system clock jumps, so consumers can reliably order records of one process. Values are only comparable within one
+    /// process run. Combine it with `with_timestamp` to also get a human-readable point in time.
+    #[must_use]
+    pub fn with_monotonic_timestamp(mut self, monotonic: bool) -> Self {
+        self.monotonic = monotonic.then(|| Monotonic {
+            start: Instant::now(),
+            last: AtomicU64::new(0),
+        });
+        self
+    }
+
+    /// Adds the fields `start_time`, holding the time this `Enrichment` was created in milliseconds since the Unix epoch,
+    /// and `uptime_ms`, holding the milliseconds elapsed since then.
+    ///
+    /// Create the `Enrichment` together with the logger at process start, so the fields approximate the process lifecycle.
+    /// `start_time` changes with every restart, which tells records of different process runs apart on a shared stream.
+    #[must_use]
+    pub fn with_uptime(mut self, uptime: bool) -> Self {
+        self.uptime = uptime.then(|| Uptime {
+            start: Instant::now(),
+            start_time: millis_since_epoch(SystemTime::now()),
+        });
+        self
+    }
+
+    /// Adds the object `build` holding the `version` and, if given, the `commit` of the application, so records can be
+    /// traced back to the build that emitted them. Pass the version of the application, e.g.
+    /// `env!("CARGO_PKG_VERSION").to_owned()` in the application crate, as this crate can only know its own version.
+    #[must_use]
+    pub fn with_build_info(mut self, version: String, commit: Option<String>) -> Self {
+        let mut build = Map::new();
+        build.insert("version".to_owned(), Value::from(version));
+        if let Some(commit) = commit {
+            build.insert("commit".to_owned(), Value::from(commit));
+        }
+        self.build = Some(Value::Object(build));
+        self
+    }
+
+    /// Adds the field `instance` holding `name`, so the records of several loggers in one process, e.g. one for audit logs
+    /// and one for application logs, can be told apart on a shared stream. Give each logger's encoder its own name.
+    #[must_use]
+    pub fn with_instance_name(mut self, name: String) -> Self {
+        self.instance = Some(name);
+        self
+    }
+
+    /// Adds the field `correlation_id` holding the id returned by `source`, e.g. a request id that a web framework keeps
+    /// in a thread-local, so all records of one request are linked. The field is left out if `source` returns `None`.
+    ///
+    /// `source` is called on the thread encoding the record, for every record, so it should be cheap. With a logger that
+    /// encodes on another thread, e.g. with `parallel_logger`, a thread-local of the logging thread is not available.
+    #[must_use]
+    pub fn with_correlation_id_source<F>(mut self, source: F) -> Self
+    where
+        F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        self.correlation_id = Some(Box::new(source));
+        self
+    }
+
+    /// Returns the configured fields for a record being encoded right now.
+    pub(crate) fn fields(&self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        if self.timestamp {
+            let now = self.clock.as_ref().map_or_else(SystemTime::now, |clock| clock());
+            fields.insert("timestamp".to_owned(), Value::from(millis_since_epoch(now)));
+        }
+        if let Some(monotonic) = &self.monotonic {
+            fields.insert("monotonic_ns".to_owned(), Value::from(monotonic.next()));
+        }
+        if let Some(uptime) = &self.uptime {
+            let elapsed = u64::try_from(uptime.start.elapsed().as_millis()).unwrap_or(u64::MAX);
+            fields.insert("start_time".to_owned(), Value::from(uptime.start_time));
+            fields.insert("uptime_ms".to_owned(), Value::from(elapsed));
+        }
+        if let Some(build) = &self.build {
+            fields.insert("build".to_owned(), build.clone());
+        }
+        if let Some(instance) = &self.instance {
+            fields.insert("instance".to_owned(), Value::from(instance.as_str()));
+        }
+        if let Some(correlation_id) = self.correlation_id.as_ref().and_then(|source| source()) {
+            fields.insert("correlation_id".to_owned(), Value::from(correlation_id));
+        }
+        fields
+    }
+
+    /// Returns the JSON Schemas of the configured fields by name, all of which except `correlation_id` are present in
+    /// every record.
+    pub(crate) fn schema_properties(&self) -> Map<String, Value> {
+        let integer = || serde_json::json!({ "type": "integer", "minimum": 0 });
+        let mut properties = Map::new();
+        if self.timestamp {
+            properties.insert("timestamp".to_owned(), integer());
+        }
+        if self.monotonic.is_some() {
+            properties.insert("monotonic_ns".to_owned(), integer());
+        }
+        if self.uptime.is_some() {
+            properties.insert("start_time".to_owned(), integer());
+            properties.insert("uptime_ms".to_owned(), integer());
+        }
+        if self.build.is_some() {
+            let build = serde_json::json!({
+                "type": "object",
+                "properties": { "version": { "type": "string" }, "commit": { "type": "string" } },
+                "required": ["version"],
+            });
+            properties.insert("build".to_owned(), build);
+        }
+        if self.instance.is_some() {
+            properties.insert("instance".to_owned(), serde_json::json!({ "type": "string" }));
+        }
+        if self.correlation_id.is_some() {
+            properties.insert("correlation_id".to_owned(), serde_json::json!({ "type": "string" }));
+        }
+        properties
+    }
+}
+
+pub(crate) fn millis_since_epoch(time: SystemTime) -> u64 {
+    u64::try_from(time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_fields_by_default() {
+        assert!(Enrichment::new().fields().is_empty());
+    }
+
+    #[test]
+    fn test_timestamp_with_clock() {
+        let enrichment = Enrichment::new()
+            .with_timestamp(true)
+            .with_clock(|| UNIX_EPOCH + std::time::Duration::from_millis(1_717_200_000_123));
+
+        assert_eq!(enrichment.fields()["timestamp"], 1_717_200_000_123_u64);
+    }
+
+    #[test]
+    fn test_monotonic_timestamp_increases() {
+        let enrichment = Enrichment::new().with_monotonic_timestamp(true).with_timestamp(true);
+
+        let values: Vec<u64> = (0..1000)
+            .map(|_| enrichment.fields()["monotonic_ns"].as_u64().unwrap())
+            .collect();
+
+        assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(enrichment.fields()["timestamp"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_uptime_increases() {
+        let enrichment = Enrichment::new().with_uptime(true);
+
+        let first = enrichment.fields();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let second = enrichment.fields();
+
+        assert!(second["uptime_ms"].as_u64().unwrap() >= first["uptime_ms"].as_u64().unwrap() + 20);
+        assert_eq!(first["start_time"], second["start_time"]);
+        assert!(first["start_time"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_build_info() {
+        let enrichment = Enrichment::new().with_build_info("1.4.2".to_owned(), Some("3f2a9c1".to_owned()));
+        assert_eq!(
+            enrichment.fields()["build"],
+            serde_json::json!({ "version": "1.4.2", "commit": "3f2a9c1" })
+        );
+
+        let enrichment = Enrichment::new().with_build_info("1.4.2".to_owned(), None);
+        assert_eq!(enrichment.fields()["build"], serde_json::json!({ "version": "1.4.2" }));
+    }
+
+    #[test]
+    fn test_correlation_id_source() {
+        thread_local! {
+            static REQUEST_ID: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+        }
+
+        let enrichment = Enrichment::new().with_correlation_id_source(|| REQUEST_ID.with(|id| id.borrow().clone()));
+        assert!(enrichment.fields().get("correlation_id").is_none());
+
+        REQUEST_ID.with(|id| *id.borrow_mut() = Some("req-7f3a".to_owned()));
+        assert_eq!(enrichment.fields()["correlation_id"], "req-7f3a");
+    }
+}
@@ -0,0 +1,454 @@
+//! # Key-Values Module
+//!
+//! This module collects the structured key-values of a `log::Record` for the default encoders.
+
+use std::fmt;
+
+use log::kv::{Error, Key, Value, VisitSource};
+use serde::{
+    ser::{self, Impossible},
+    Serialize, Serializer,
+};
+use serde_json::Map;
+
+use super::Record;
+
+/// The key-values of a record, converted to JSON.
+#[derive(Debug, Default)]
+pub(crate) struct KeyValues {
+    /// All key-values by key. Errors are represented by their `Display` output. Byte values are represented as a string if
+    /// they are valid UTF-8, or else as an object with their base64 encoding in `base64`.
+    pub(crate) fields: Map<String, serde_json::Value>,
+    /// The messages of the first error-typed value and all of its sources, outermost first.
+    pub(crate) error_chain: Option<Vec<String>>,
+}
+
+impl KeyValues {
+    pub(crate) fn collect(record: &Record) -> Self {
+        let mut key_values = Self::default();
+        // visiting only fails if the visitor returns an error, which ours never does
+        let _ = record.key_values().visit(&mut key_values);
+        key_values
+    }
+}
+
+impl<'kvs> VisitSource<'kvs> for KeyValues {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        let json = match value.to_borrowed_error() {
+            Some(error) => {
+                if self.error_chain.is_none() {
+                    self.error_chain = Some(error_chain(error));
+                }
+                serde_json::Value::from(error.to_string())
+            }
+            None => match value.serialize(BytesSerializer) {
+                Ok(bytes) => bytes_value(bytes),
+                Err(_) => serde_json::to_value(&value).unwrap_or_else(|_| serde_json::Value::from(value.to_string())),
+            },
+        };
+        self.fields.insert(key.as_str().to_owned(), json);
+        Ok(())
+    }
+}
+
+/// Renders the fields as one string of `key=value` pairs joined by `separator` (logfmt with a space), e.g.
+/// `user=alice id=5 query="SELECT 1"`. Strings are written as they are, other values as JSON. Keys and values that are
+/// empty or contain whitespace, `=`, `"`, `\` or the separator are quoted as JSON strings, which escapes `"`, `\` and
+/// control characters.
+pub(crate) fn flat_fields(fields: &Map<String, serde_json::Value>, separator: char) -> String {
+    let quoted = |text: &str| {
+        let needs_quotes = text.is_empty()
+            || text
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '=' | '"' | '\\') || c == separator);
+        if needs_quotes {
+            serde_json::Value::from(text).to_string()
+        } else {
+            text.to_owned()
+        }
+    };
+    let mut flat = String::new();
+    for (key, value) in fields {
+        if !flat.is_empty() {
+            flat.push(separator);
+        }
+        let value = match value {
+            serde_json::Value::String(value) => quoted(value),
+            value => quoted(&value.to_string()),
+        };
+        flat.push_str(&quoted(key));
+        flat.push('=');
+        flat.push_str(&value);
+    }
+    flat
+}
+
+/// Nests the fields with dotted keys into objects, e.g. `http.method` and `http.path` into `{"http":{"method":..,"path":..}}`.
+///
+/// Keys with fewer parts are nested first, so on a collision the value at the shorter path wins and the conflicting key
+/// is kept flat with its full name, e.g. `http.method` next to a scalar `http`. Keys with an empty part, e.g. `http.` or
+/// `.method`, are kept flat as well.
+pub(crate) fn nest_fields(fields: Map<String, serde_json::Value>) -> Map<String, serde_json::Value> {
+    let mut fields: Vec<_> = fields.into_iter().collect();
+    fields.sort_by_key(|(key, _)| key.split('.').count());
+    let mut nested = Map::new();
+    for (key, value) in fields {
+        if key.split('.').any(str::is_empty) {
+            nested.insert(key, value);
+        } else if let Err(value) = insert_nested(&mut nested, &key, value) {
+            nested.insert(key, value);
+        }
+    }
+    nested
+}
+
+/// Inserts the value at the path of the dotted key, or returns it if the path is taken or leads through a non-object.
+fn insert_nested(
+    mut map: &mut Map<String, serde_json::Value>,
+    key: &str,
+    value: serde_json::Value,
+) -> Result<(), serde_json::Value> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let leaf = parts.pop().unwrap_or_default();
+    for part in parts {
+        match map.entry(part).or_insert_with(|| serde_json::Value::Object(Map::new())) {
+            serde_json::Value::Object(child) => map = child,
+            _ => return Err(value),
+        }
+    }
+    if map.contains_key(leaf) {
+        return Err(value);
+    }
+    map.insert(leaf.to_owned(), value);
+    Ok(())
+}
+
+/// Converts bytes into a JSON string, base64 encoded if they are not valid UTF-8, as JSON strings can't hold other bytes.
+fn bytes_value(bytes: Vec<u8>) -> serde_json::Value {
+    match String::from_utf8(bytes) {
+        Ok(string) => serde_json::Value::from(string),
+        Err(error) => serde_json::json!({ "base64": base64(error.as_bytes()) }),
+    }
+}
+
+/// Encodes the bytes with the standard base64 alphabet and padding (RFC 4648).
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (i, &byte)| group | u32::from(byte) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(group >> (18 - 6 * i)) as usize & 0x3f]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// A serializer that only accepts byte values, to tell them apart from sequences of numbers, which `serde_json`
+/// serializes them as.
+struct BytesSerializer;
+
+#[derive(Debug)]
+struct NotBytes;
+
+impl fmt::Display for NotBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("not bytes")
+    }
+}
+
+impl std::error::Error for NotBytes {}
+
+impl ser::Error for NotBytes {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        Self
+    }
+}
+
+macro_rules! not_bytes {
+    ($($method:ident($($ty:ty),*)),* $(,)?) => {
+        $(fn $method(self, $(_: $ty),*) -> Result<Self::Ok, Self::Error> {
+            Err(NotBytes)
+        })*
+    };
+}
+
+impl Serializer for BytesSerializer {
+    type Ok = Vec<u8>;
+    type Error = NotBytes;
+    type SerializeSeq = Impossible<Vec<u8>, NotBytes>;
+    type SerializeTuple = Impossible<Vec<u8>, NotBytes>;
+    type SerializeTupleStruct = Impossible<Vec<u8>, NotBytes>;
+    type SerializeTupleVariant = Impossible<Vec<u8>, NotBytes>;
+    type SerializeMap = Impossible<Vec<u8>, NotBytes>;
+    type SerializeStruct = Impossible<Vec<u8>, NotBytes>;
+    type SerializeStructVariant = Impossible<Vec<u8>, NotBytes>;
+
+    fn serialize_bytes(self, bytes: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(bytes.to_vec())
+    }
+
+    not_bytes! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_none(),
+        serialize_unit(),
+        serialize_unit_struct(&'static str),
+        serialize_unit_variant(&'static str, u32, &'static str),
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<Self::Ok, Self::Error> {
+        Err(NotBytes)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(NotBytes)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(NotBytes)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(NotBytes)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(NotBytes)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(NotBytes)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(NotBytes)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(NotBytes)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(NotBytes)
+    }
+}
+
+/// Returns the messages of `error` and all of its sources, outermost first.
+fn error_chain(error: &(dyn std::error::Error + 'static)) -> Vec<String> {
+    let mut chain = vec![error.to_string()];
+    let mut source = error.source();
+    while let Some(error) = source {
+        chain.push(error.to_string());
+        source = error.source();
+    }
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt;
+
+    #[derive(Debug)]
+    struct TestError {
+        message: &'static str,
+        source: Option<Box<TestError>>,
+    }
+
+    impl fmt::Display for TestError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.message)
+        }
+    }
+
+    impl std::error::Error for TestError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            self.source
+                .as_deref()
+                .map(|source| source as &(dyn std::error::Error + 'static))
+        }
+    }
+
+    #[test]
+    fn test_collect_key_values() {
+        let key_values = [("user", Value::from("alice")), ("id", Value::from(5u64))];
+        let record = Record::builder()
+            .args(format_args!("Logged in"))
+            .key_values(&key_values)
+            .build();
+
+        let collected = KeyValues::collect(&record);
+
+        assert_eq!(collected.fields["user"], "alice");
+        assert_eq!(collected.fields["id"], 5);
+        assert!(collected.error_chain.is_none());
+    }
+
+    struct Bytes(&'static [u8]);
+
+    impl Serialize for Bytes {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    #[test]
+    fn test_collect_bytes() {
+        let key_values = [
+            ("text", Value::from_serde(&Bytes(b"hello"))),
+            ("binary", Value::from_serde(&Bytes(&[0xff, 0xfe, 0x00, 0x61]))),
+            ("numbers", Value::from_serde(&[1u8, 2])),
+        ];
+        let record = Record::builder().args(format_args!("Bytes")).key_values(&key_values).build();
+
+        let collected = KeyValues::collect(&record);
+
+        assert_eq!(collected.fields["text"], "hello");
+        assert_eq!(collected.fields["binary"], serde_json::json!({ "base64": "//4AYQ==" }));
+        assert_eq!(collected.fields["numbers"], serde_json::json!([1, 2]));
+    }
+
+    #[test]
+    fn test_flat_fields() {
+        let fields = serde_json::json!({
+            "user": "alice",
+            "id": 5,
+            "query": "SELECT \"name\" FROM users",
+            "path": "C:\\logs",
+            "empty": "",
+            "tags": ["a", "b"],
+            "ok": true,
+            "none": null,
+        });
+        let serde_json::Value::Object(fields) = fields else {
+            unreachable!()
+        };
+
+        assert_eq!(
+            flat_fields(&fields, ' '),
+            r#"empty="" id=5 none=null ok=true path="C:\\logs" query="SELECT \"name\" FROM users" tags="[\"a\",\"b\"]" user=alice"#
+        );
+        let fields = serde_json::json!({ "a": "x,y", "b": "x y" });
+        let serde_json::Value::Object(fields) = fields else {
+            unreachable!()
+        };
+        assert_eq!(flat_fields(&fields, ','), r#"a="x,y",b="x y""#);
+    }
+
+    #[test]
+    fn test_nest_fields() {
+        let fields = serde_json::json!({
+            "http.method": "GET",
+            "http.path": "/users",
+            "http.response.status": 200,
+            "user": "alice",
+        });
+        let serde_json::Value::Object(fields) = fields else {
+            unreachable!()
+        };
+
+        assert_eq!(
+            serde_json::Value::Object(nest_fields(fields)),
+            serde_json::json!({
+                "http": { "method": "GET", "path": "/users", "response": { "status": 200 } },
+                "user": "alice",
+            })
+        );
+    }
+
+    #[test]
+    fn test_nest_fields_collisions() {
+        let fields = serde_json::json!({
+            "http.method": "GET",
+            "http": "scalar",
+            "db.query": "SELECT 1",
+            "db.query.duration": 5,
+            "trailing.": 1,
+        });
+        let serde_json::Value::Object(fields) = fields else {
+            unreachable!()
+        };
+
+        assert_eq!(
+            serde_json::Value::Object(nest_fields(fields)),
+            serde_json::json!({
+                "http": "scalar",
+                "http.method": "GET",
+                "db": { "query": "SELECT 1" },
+                "db.query.duration": 5,
+                "trailing.": 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_collect_error_chain() {
+        let error = TestError {
+            message: "request failed",
+            source: Some(Box::new(TestError {
+                message: "connection reset",
+                source: None,
+            })),
+        };
+        let key_values = [("error", Value::from_dyn_error(&error))];
+        let record = Record::builder().args(format_args!("Failed")).key_values(&key_values).build();
+
+        let collected = KeyValues::collect(&record);
+
+        assert_eq!(collected.fields["error"], "request failed");
+        assert_eq!(
+            collected.error_chain,
+            Some(vec!["request failed".to_owned(), "connection reset".to_owned()])
+        );
+    }
+}
@@ -26,6 +26,9 @@
 //! They are used by `RedisLogger` to encode the messages before sending them to Redis.
 //! The module provides default implementations of these traits when the feature `default_encoders` is enabled,
 //! but users can also provide their own implementations.
+//! Without any feature, `CsvPubSubEncoder` encodes records as CSV lines, `CompositeStreamEncoder` and
+//! `HybridStreamEncoder` combine other encoders into one stream entry, and `LevelDispatchEncoder` chooses the encoder of
+//! a record by its level.
 //!
 //! ## Usage
 //!
@@ -39,7 +42,7 @@
 //! shows how to configure `RedisLogger` to use this encoder while being part of multiple loggers that run on a separate thread using `parallel_logger`.
 //! ```rust,ignore
 //! struct BincodeRedisEncoder;
-//! 
+//!
 //! impl PubSubEncoder for BincodeRedisEncoder {
 //!     fn encode(&self, record: &log::Record) -> Vec<u8> {
 //!         let mut slice = [0u8; 2000];
@@ -49,11 +52,11 @@
 //!         slice.to_vec()
 //!     }
 //! }
-//! 
+//!
 //! fn main() {
 //!     let redis_client = redis::Client::open(REDIS_URL).unwrap();
 //!     let redis_connection = redis_client.get_connection().unwrap();
-//! 
+//!
 //!     ParallelLogger::init(
 //!         log::LevelFilter::Debug,
 //!         ParallelMode::Sequential,
@@ -78,13 +81,64 @@
 //!
 //! This module has a feature flag `default_encoders` that, when enabled, provides default implementations
 //! of `PubSubEncoder` and `StreamEncoder` that encode the log messages as JSON or as a vector of tuples, respectively.
+//! It also provides the `LokiStreamEncoder` that splits records into a `labels` and a `line` field for Grafana Loki shippers.
+//! The `ErrorTrackingStreamEncoder` writes entries with a fixed layout for error-tracking consumers, filled from the `error` key-value.
+//! The `EnvelopeEncoder` wraps the messages of any pub/sub encoder in a versioned JSON envelope.
+//! The default encoders can add further fields like timestamps to every record, configured with `Enrichment`.
 //!
 //! Another feature flag `shared_logger` implements the `simplelog::SharedLogger` trait for `RedisLogger`. This enables use in a `simplelog::CombinedLogger`.
+//!
+//! The feature flag `ecs` provides the `EcsPubSubEncoder` that encodes records as Elastic Common Schema (ECS) compatible JSON.
+//!
+//! The feature flag `dated_streams` enables `RedisLoggerConfig::with_dated_stream` which writes every record to a stream named
+//! after the current date for retention by day.
+//!
+//! The feature flag `time_series` enables `RedisLoggerConfig::with_time_series` which adds numeric key-values of records
+//! to RedisTimeSeries keys.
+//!
+//! The feature flag `signal` provides `install_flush_signal_handler` to flush the global logger on a Unix signal.
+//!
+//! The feature flag `socks5` provides `StreamConnection`, a connection over a caller-supplied stream, which can connect to Redis
+//! through a SOCKS5 proxy for networks where Redis is not directly reachable.
+//!
+//! The feature flag `tcp_nodelay` enables the feature of the same name of `redis`, which sets `TCP_NODELAY` on every TCP
+//! connection `redis` creates (and on the connections of `StreamConnection::connect_socks5`), so small writes like single
+//! records are sent without the delay of Nagle's algorithm. TCP keepalive is enabled by the default feature `keep-alive`
+//! of `redis` with the system's default intervals. `redis` 0.24 offers neither setting per connection, so they can't be
+//! configured on `RedisLoggerConfig`.
+//!
+//! The feature flag `config_file` provides `RedisLogger::from_file`, which configures a logger with the default encoders
+//! from a TOML or JSON file (see `RedisLoggerFileConfig`).
+//!
+//! The feature flag `testing` provides `CapturingLogger`, which runs a logger against an in-memory connection and reads
+//! back the commands it sent, to unit test encoders and routing without a Redis server.
+//!
+//! The feature flag `compression` provides `Compression` to compress the payload of `HybridStreamEncoder` with zlib, LZ4
+//! or gzip, `CompressedPubSubEncoder` and `RedisLoggerConfig::with_compressed_channel` to publish compressed messages.
+//!
+//! The feature flag `avro` provides `AvroPubSubEncoder`, which encodes records as binary Apache Avro following a given
+//! schema, optionally with the schema registry framing.
 
-use std::sync::Mutex;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+    fmt,
+    io::{self, Write},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
-use redis::ConnectionLike;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use redis::{ConnectionLike, ErrorKind, RedisError};
+use spool::DiskSpool;
 
 #[cfg_attr(docsrs, doc(cfg(feature = "default_encoders")))]
 #[cfg(feature = "default_encoders")]
@@ -92,21 +146,117 @@ mod defaults;
 #[cfg(feature = "default_encoders")]
 pub use defaults::*;
 
+#[cfg_attr(docsrs, doc(cfg(feature = "default_encoders")))]
+#[cfg(feature = "default_encoders")]
+mod enrichment;
+#[cfg(feature = "default_encoders")]
+mod key_values;
+#[cfg(feature = "default_encoders")]
+pub use enrichment::*;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "config_file")))]
+#[cfg(feature = "config_file")]
+mod config_file;
+#[cfg(feature = "config_file")]
+pub use config_file::*;
+
+mod composite;
+pub use composite::*;
+
+mod csv;
+pub use csv::*;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+#[cfg(any(test, feature = "testing"))]
+mod testing;
+#[cfg(any(test, feature = "testing"))]
+pub use testing::*;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+#[cfg(feature = "compression")]
+mod compression;
+#[cfg(feature = "compression")]
+pub use compression::*;
+
+mod macros;
+mod spool;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "avro")))]
+#[cfg(feature = "avro")]
+mod avro;
+#[cfg(feature = "avro")]
+pub use avro::*;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "ecs")))]
+#[cfg(feature = "ecs")]
+mod ecs;
+#[cfg(feature = "ecs")]
+pub use ecs::*;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "time_series")))]
+#[cfg(feature = "time_series")]
+mod time_series;
+#[cfg(feature = "time_series")]
+pub use time_series::*;
+
+#[cfg_attr(docsrs, doc(cfg(all(unix, feature = "signal"))))]
+#[cfg(all(unix, feature = "signal"))]
+mod signal;
+#[cfg(all(unix, feature = "signal"))]
+pub use signal::*;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "socks5")))]
+#[cfg(feature = "socks5")]
+mod socks5;
+#[cfg(feature = "socks5")]
+pub use socks5::*;
+
 #[cfg(test)]
 mod lib_tests;
 
 /// Trait for encoding log messages to be published to a pub/sub channel.
-pub trait PubSubEncoder: Send + Sync + Sized {
+pub trait PubSubEncoder: Send + Sync {
     /// Encodes the given `log::Record` into a byte vector.
     fn encode(&self, record: &Record) -> Vec<u8>;
 }
 
 /// Trait for encoding log messages to be added to a Redis stream.
-pub trait StreamEncoder: Send + Sync + Sized {
+pub trait StreamEncoder: Send + Sync {
     /// Encodes the given `log::Record` into a vector of tuples of a field name and the corresponding value as a byte vector.
     fn encode(&self, record: &Record) -> Vec<(String, Vec<u8>)>;
 }
 
+/// Trait for hashing log records, used to choose the channel with `RedisLoggerConfig::with_sharded_channels` and to
+/// identify duplicates with `RedisLoggerConfig::with_redis_dedup`. Records with the same hash are considered the same.
+pub trait RecordHasher: Send + Sync {
+    /// Hashes the given `log::Record`. The hash should be stable across processes if several instances share Redis.
+    fn hash(&self, record: &Record) -> u64;
+}
+
+/// `DefaultRecordHasher` hashes the level, target and message of a record with FNV-1a, which is stable across processes
+/// and platforms, so that all instances of a service derive the same hash for the same record.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct DefaultRecordHasher {}
+
+impl DefaultRecordHasher {
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+impl RecordHasher for DefaultRecordHasher {
+    fn hash(&self, record: &Record) -> u64 {
+        let message = record.args().to_string();
+        [record.level().as_str(), record.target(), &message]
+            .iter()
+            .flat_map(|part| part.bytes().chain(std::iter::once(0)))
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+}
+
 /// Placeholder. Cannot be instantiated or used. Necessary as a placeholder when not specifing a pub/sub encoder.
 #[derive(Debug)]
 #[doc(hidden)]
@@ -133,7 +283,178 @@ impl StreamEncoder for DummyStreamEncoder {
     }
 }
 
+/// An error that occurred while logging a record to Redis. Passed to the error handler set with
+/// `RedisLoggerConfig::with_error_handler`, or printed to stderr if no handler is set.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RedisLoggerError {
+    /// Redis rejected the commands or the connection failed. `retryable` tells whether the error was classified as transient
+    /// by `is_retryable` and has already been retried as often as configured. `target` names the failed channel or stream
+    /// if targets are written independently.
+    Redis {
+        error: RedisError,
+        retryable: bool,
+        target: Option<String>,
+    },
+    /// The value of the remote level key (see `RedisLoggerConfig::with_remote_level_key`) is not a valid level.
+    /// The current level is kept.
+    InvalidRemoteLevel { key: String, value: String },
+    /// The disk spool (see `RedisLoggerConfig::with_disk_spool`) could not be read or written, or it is full.
+    Spool { path: PathBuf, error: io::Error },
+    /// A message was published to a channel without subscribers, so nobody received it
+    /// (see `RedisLoggerConfig::with_warn_on_no_subscribers`).
+    NoSubscribers { channel: String },
+    /// Writing failed `failures` times in a row, exceeding the budget set with `RedisLoggerConfig::with_max_total_retries`.
+    /// The logger drops all records until `RedisLogger::reconnect` succeeds.
+    Disabled { failures: u64 },
+    /// The `pub/sub`, `stream`, `queue` or `custom command` encoder, or the `dynamic channels` closure panicked with `message` while encoding a record. The record is not
+    /// written to the targets of that encoder, but still to the targets of the other ones.
+    EncoderPanic { encoder: &'static str, message: String },
+    /// The first error reported after `suppressed` errors were left out by `RedisLoggerConfig::with_error_throttle`.
+    Throttled { error: Box<RedisLoggerError>, suppressed: u64 },
+    /// A logger was started while logger configurations held `live` connections, more than the `max` set with
+    /// `set_max_live_connections`, which hints at loggers that are created but never dropped.
+    TooManyConnections { live: usize, max: usize },
+}
+
+impl fmt::Display for RedisLoggerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Redis {
+                error,
+                retryable,
+                target,
+            } => {
+                write!(f, "Error logging to Redis")?;
+                if let Some(target) = target {
+                    write!(f, " target '{target}'")?;
+                }
+                if *retryable {
+                    write!(f, " (retryable)")?;
+                }
+                write!(f, ": {error}")
+            }
+            Self::InvalidRemoteLevel { key, value } => {
+                write!(f, "Ignoring invalid log level '{value}' read from Redis key '{key}'")
+            }
+            Self::Spool { path, error } => write!(f, "Error accessing the disk spool '{}': {error}", path.display()),
+            Self::NoSubscribers { channel } => write!(f, "No subscribers received the message published to '{channel}'"),
+            Self::EncoderPanic { encoder, message } => {
+                write!(f, "The {encoder} encoder panicked, skipping its targets: {message}")
+            }
+            Self::Disabled { failures } => write!(
+                f,
+                "Disabled logging to Redis after {failures} failed attempts in a row, reconnect to enable it again"
+            ),
+            Self::Throttled { error, suppressed } => write!(f, "{error} ({suppressed} more errors suppressed)"),
+            Self::TooManyConnections { live, max } => write!(
+                f,
+                "{live} Redis connections are held by loggers, more than the maximum of {max}, are loggers leaked?"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RedisLoggerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Redis { error, .. } => Some(error),
+            Self::InvalidRemoteLevel { .. }
+            | Self::NoSubscribers { .. }
+            | Self::Disabled { .. }
+            | Self::EncoderPanic { .. }
+            | Self::TooManyConnections { .. } => None,
+            Self::Spool { error, .. } => Some(error),
+            Self::Throttled { error, .. } => Some(error),
+        }
+    }
+}
+
+/// An error returned by `RedisLogger::simple`.
+#[cfg_attr(docsrs, doc(cfg(feature = "default_encoders")))]
+#[cfg(feature = "default_encoders")]
 #[derive(Debug)]
+#[non_exhaustive]
+pub enum InitError {
+    /// The URL is invalid or the connection to Redis could not be established.
+    Redis(RedisError),
+    /// Another logger has already been set as the global logger.
+    SetLogger(SetLoggerError),
+}
+
+#[cfg(feature = "default_encoders")]
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Redis(error) => write!(f, "Error connecting to Redis: {error}"),
+            Self::SetLogger(error) => write!(f, "Error setting the global logger: {error}"),
+        }
+    }
+}
+
+#[cfg(feature = "default_encoders")]
+impl std::error::Error for InitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Redis(error) => Some(error),
+            Self::SetLogger(error) => Some(error),
+        }
+    }
+}
+
+#[cfg(feature = "default_encoders")]
+impl From<RedisError> for InitError {
+    fn from(error: RedisError) -> Self {
+        Self::Redis(error)
+    }
+}
+
+#[cfg(feature = "default_encoders")]
+impl From<SetLoggerError> for InitError {
+    fn from(error: SetLoggerError) -> Self {
+        Self::SetLogger(error)
+    }
+}
+
+/// The number of connections held by all logger configurations of the process, see `live_connections`.
+static LIVE_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of live connections above which starting a logger is reported, 0 for no limit.
+static MAX_LIVE_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of connections currently held by logger configurations in this process, i.e. by every
+/// `RedisLoggerConfig` and `RedisLogger` that has not been dropped yet.
+pub fn live_connections() -> usize {
+    LIVE_CONNECTIONS.load(Ordering::Relaxed)
+}
+
+/// Sets how many connections logger configurations may hold in this process before starting another logger is reported
+/// as `RedisLoggerError::TooManyConnections` to its error handler, e.g. to catch loggers created per test or per tenant
+/// and never dropped before they exhaust the client limit of Redis. The logger is started anyway. `None` disables the
+/// check, which is the default.
+///
+/// A `RedisLogger::reload` holds the connections of both the old and the new configuration while starting the new one.
+pub fn set_max_live_connections(max: Option<usize>) {
+    MAX_LIVE_CONNECTIONS.store(max.map_or(0, |max| max.max(1)), Ordering::Relaxed);
+}
+
+/// Classifies a `RedisError` as transient (worth retrying) or permanent.
+///
+/// IO errors (dropped or refused connections, timeouts) and the server-side states `TRYAGAIN`, `LOADING`, `CLUSTERDOWN`
+/// and `MASTERDOWN` are considered transient. Everything else, like `WRONGTYPE` or `NOAUTH`, won't fix itself by retrying.
+#[must_use]
+pub fn is_retryable(error: &RedisError) -> bool {
+    error.is_io_error()
+        || matches!(
+            error.kind(),
+            ErrorKind::IoError
+                | ErrorKind::TryAgain
+                | ErrorKind::BusyLoadingError
+                | ErrorKind::ClusterDown
+                | ErrorKind::MasterDown
+        )
+}
+
 /// A logger that logs messages to Redis.
 pub struct RedisLogger<CONN, PUBSUB, STREAM>
 where
@@ -141,8 +462,79 @@ where
     PUBSUB: PubSubEncoder,
     STREAM: StreamEncoder,
 {
-    level: LevelFilter,
-    config: RedisLoggerConfig<CONN, PUBSUB, STREAM>,
+    level: Arc<AtomicLevel>,
+    /// Only locked to clone or replace the `Arc`, so no lock is held while a record is written and the error handler is
+    /// free to log through this logger.
+    config: RwLock<Arc<RedisLoggerConfig<CONN, PUBSUB, STREAM>>>,
+    counters: RecordCounters,
+}
+
+/// Counts the records passed to `log`, see `RedisLogger::stats`. Kept outside the configuration, so the counts survive
+/// a `reload`.
+#[derive(Debug, Default)]
+struct RecordCounters {
+    seen: AtomicU64,
+    skipped: AtomicU64,
+    emitted: AtomicU64,
+}
+
+/// How many records a logger has seen and what became of them, as returned by `RedisLogger::stats`.
+///
+/// Records that are neither skipped nor emitted were dropped by sampling, shedding or deduplication, or had no channel
+/// or stream to go to, or arrived while the logger was disabled by `RedisLoggerConfig::with_max_total_retries`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LoggerStats {
+    /// The number of records passed to `log`.
+    pub seen: u64,
+    /// The number of records skipped because their level is above the logger's level or their target is ignored.
+    pub skipped: u64,
+    /// The number of records whose commands were sent to Redis, whether writing them succeeded or not.
+    pub emitted: u64,
+}
+
+/// A `LevelFilter` that can be changed at runtime.
+#[derive(Debug)]
+struct AtomicLevel(AtomicUsize);
+
+impl AtomicLevel {
+    fn new(level: LevelFilter) -> Self {
+        Self(AtomicUsize::new(level as usize))
+    }
+
+    fn get(&self) -> LevelFilter {
+        LevelFilter::iter()
+            .nth(self.0.load(Ordering::Relaxed))
+            .unwrap_or(LevelFilter::Trace)
+    }
+
+    /// Sets the level and raises the max level of `log` if needed, as `log` drops records above it before any logger
+    /// sees them.
+    fn set(&self, level: LevelFilter) {
+        self.0.store(level as usize, Ordering::Relaxed);
+        if level > log::max_level() {
+            log::set_max_level(level);
+        }
+    }
+}
+
+/// Forwards records to several loggers, registered by `RedisLogger::init_with`.
+struct Multiplexer(Vec<Box<dyn Log>>);
+
+impl Log for Multiplexer {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.iter().any(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        for logger in self.0.iter().filter(|logger| logger.enabled(record.metadata())) {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.0.iter().for_each(|logger| logger.flush());
+    }
 }
 
 impl<CONN, PUBSUB, STREAM> RedisLogger<CONN, PUBSUB, STREAM>
@@ -162,87 +554,2740 @@ where
     ///
     /// A boxed instance of `RedisLogger`, not yet initialized as the global logger.
     pub fn new(level: LevelFilter, config: RedisLoggerConfig<CONN, PUBSUB, STREAM>) -> Box<Self> {
-        Box::new(Self { level, config })
+        let level = Arc::new(AtomicLevel::new(level));
+        Self::start(&level, &config);
+        Box::new(Self {
+            level,
+            config: RwLock::new(Arc::new(config)),
+            counters: RecordCounters::default(),
+        })
+    }
+
+    /// Starts using a configuration: spawns the poller of the remote level key and sets the client name, if configured.
+    /// Reports if more connections are live than allowed by `set_max_live_connections`.
+    fn start(level: &Arc<AtomicLevel>, config: &RedisLoggerConfig<CONN, PUBSUB, STREAM>) {
+        let (live, max) = (live_connections(), MAX_LIVE_CONNECTIONS.load(Ordering::Relaxed));
+        if max > 0 && live > max {
+            config.report(RedisLoggerError::TooManyConnections { live, max });
+        }
+        if let Some((key, poll_interval)) = &config.remote_level {
+            Self::spawn_remote_level_poller(
+                key.clone(),
+                *poll_interval,
+                Arc::downgrade(level),
+                Arc::downgrade(&config.connection),
+                config.error_handler.clone(),
+            );
+        }
+        if let Some((maxlen, interval)) = config.background_trim {
+            Self::spawn_background_trim(
+                config.static_streams(),
+                maxlen,
+                interval,
+                Arc::downgrade(&config.connection),
+                config.error_handler.clone(),
+            );
+        }
+        if let Some(heartbeat) = &config.idle_heartbeat {
+            Self::spawn_idle_heartbeat(
+                config.static_streams(),
+                (config.stream_trim.no_mkstream, config.stream_trim.maxlen),
+                IdleHeartbeat {
+                    interval: heartbeat.interval,
+                    start: heartbeat.start,
+                    last_entry: Arc::clone(&heartbeat.last_entry),
+                },
+                Arc::downgrade(&config.connection),
+                config.error_handler.clone(),
+            );
+        }
+        // this unwrap only panics if the connection is poisoned, so we can't do much anyway and will panic, too!
+        let named = config.set_client_name(&mut *config.connection.lock().unwrap());
+        // reported with the connection unlocked, as the error handler may log through this logger
+        if let Err(error) = named {
+            config.report(RedisLoggerError::Redis {
+                retryable: is_retryable(&error),
+                error,
+                target: None,
+            });
+        }
+    }
+
+    /// Replaces the configuration of the logger, e.g. with new channels or encoder options read from a changed config
+    /// file, without replacing the logger itself, which is impossible once it is the global logger. The level is kept,
+    /// use `set_level` to change it, too.
+    ///
+    /// Records being logged while the configuration is replaced are completely written with the old one, and all records
+    /// logged afterwards with the new one. The records in the retry buffer of the old configuration are written with
+    /// its connection one last time, and those that still can't be written are returned, as they can't be moved to the
+    /// new configuration's targets. The old configuration is dropped with its connection once the records being logged
+    /// with it are written, which also stops the poller of its remote level key.
+    pub fn reload(&self, config: RedisLoggerConfig<CONN, PUBSUB, STREAM>) -> Vec<BufferedRecord> {
+        Self::start(&self.level, &config);
+        // this unwrap only panics if the lock is poisoned, so we can't do much anyway and will panic, too!
+        let old = std::mem::replace(&mut *self.config.write().unwrap(), Arc::new(config));
+        old.flush();
+        old.take_buffered()
+    }
+
+    /// Sets the level of the logger, e.g. to a new level read from a changed config file together with `reload`.
+    /// Raises the max level of `log` if needed, as `log` drops records above it before any logger sees them, but never
+    /// lowers it, as other loggers may need it. A remote level key set with `RedisLoggerConfig::with_remote_level_key`
+    /// overrides the level on its next poll.
+    pub fn set_level(&self, level: LevelFilter) {
+        self.level.set(level);
+    }
+
+    /// Spawns a thread that reads the level from `key` every `poll_interval` until the logger is dropped.
+    fn spawn_remote_level_poller(
+        key: String,
+        poll_interval: Duration,
+        level: Weak<AtomicLevel>,
+        connection: Weak<Mutex<CONN>>,
+        error_handler: Option<ErrorHandler>,
+    ) {
+        let report = move |error: RedisLoggerError| match &error_handler {
+            Some(handler) => handler(&error),
+            None => eprintln!("{error}"),
+        };
+        thread::spawn(move || loop {
+            let (Some(level), Some(connection)) = (level.upgrade(), connection.upgrade()) else {
+                return;
+            };
+            // this unwrap only panics if the connection is poisoned, so we can't do much anyway and will panic, too!
+            let value = redis::cmd("GET")
+                .arg(&key)
+                .query::<Option<String>>(&mut *connection.lock().unwrap());
+            match value {
+                Ok(Some(value)) => match LevelFilter::from_str(value.trim()) {
+                    Ok(new_level) => level.set(new_level),
+                    Err(_) => report(RedisLoggerError::InvalidRemoteLevel { key: key.clone(), value }),
+                },
+                Ok(None) => {}
+                Err(error) => report(RedisLoggerError::Redis {
+                    retryable: is_retryable(&error),
+                    error,
+                    target: Some(key.clone()),
+                }),
+            }
+            drop((level, connection));
+            thread::sleep(poll_interval);
+        });
+    }
+
+    /// Spawns a thread that trims `streams` to about `maxlen` entries every `interval` until the logger is dropped.
+    fn spawn_background_trim(
+        streams: Vec<String>,
+        maxlen: usize,
+        interval: Duration,
+        connection: Weak<Mutex<CONN>>,
+        error_handler: Option<ErrorHandler>,
+    ) {
+        let report = move |error: RedisLoggerError| match &error_handler {
+            Some(handler) => handler(&error),
+            None => eprintln!("{error}"),
+        };
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let Some(connection) = connection.upgrade() else {
+                return;
+            };
+            for stream in &streams {
+                // this unwrap only panics if the connection is poisoned, so we can't do much anyway and will panic, too!
+                let trimmed = redis::cmd("XTRIM")
+                    .arg(stream)
+                    .arg("MAXLEN")
+                    .arg("~")
+                    .arg(maxlen)
+                    .query::<()>(&mut *connection.lock().unwrap());
+                if let Err(error) = trimmed {
+                    report(RedisLoggerError::Redis {
+                        retryable: is_retryable(&error),
+                        error,
+                        target: Some(stream.clone()),
+                    });
+                }
+            }
+        });
+    }
+
+    /// Spawns a thread that adds a heartbeat entry to `streams` whenever no record was written for the heartbeat
+    /// interval, until the logger is dropped. The `XADD`s get `NOMKSTREAM` and `MAXLEN ~` as given by `options`.
+    fn spawn_idle_heartbeat(
+        streams: Vec<String>,
+        (no_mkstream, maxlen): (bool, Option<usize>),
+        heartbeat: IdleHeartbeat,
+        connection: Weak<Mutex<CONN>>,
+        error_handler: Option<ErrorHandler>,
+    ) {
+        let report = move |error: RedisLoggerError| match &error_handler {
+            Some(handler) => handler(&error),
+            None => eprintln!("{error}"),
+        };
+        thread::spawn(move || loop {
+            let idle = heartbeat.idle();
+            if idle < heartbeat.interval {
+                thread::sleep(heartbeat.interval - idle);
+                continue;
+            }
+            let Some(connection) = connection.upgrade() else {
+                return;
+            };
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+            for stream in &streams {
+                let mut xadd = redis::cmd("XADD");
+                xadd.arg(stream);
+                if no_mkstream {
+                    xadd.arg("NOMKSTREAM");
+                }
+                if let Some(maxlen) = maxlen {
+                    xadd.arg("MAXLEN").arg("~").arg(maxlen);
+                }
+                xadd.arg("*").arg("heartbeat").arg(now.to_string());
+                // this unwrap only panics if the connection is poisoned, so we can't do much anyway and will panic, too!
+                if let Err(error) = xadd.query::<()>(&mut *connection.lock().unwrap()) {
+                    report(RedisLoggerError::Redis {
+                        retryable: is_retryable(&error),
+                        error,
+                        target: Some(stream.clone()),
+                    });
+                }
+            }
+            drop(connection);
+            heartbeat.touch();
+        });
+    }
+
+    /// Creates a new instance of `RedisLogger` like `new`, for registering it yourself instead of with `init`.
+    ///
+    /// This is the handoff point between construction and global registration: the returned logger is fully started but
+    /// not registered, so it can be passed to `log::set_boxed_logger`, leaked with `Box::leak` for `log::set_logger`,
+    /// wrapped by a custom logging facade or combined with other loggers. Registering it is then up to the caller,
+    /// including calling `log::set_max_level`, as `log` drops all records above the max level before any logger sees them.
+    ///
+    /// ```rust,no_run
+    /// # use log::{LevelFilter, Log, Metadata, Record};
+    /// # use redis_logger::{DummyStreamEncoder, PubSubEncoder, RedisLogger, RedisLoggerConfigBuilder};
+    /// # struct MessageEncoder;
+    /// # impl PubSubEncoder for MessageEncoder {
+    /// #     fn encode(&self, record: &Record) -> Vec<u8> {
+    /// #         record.args().to_string().into_bytes()
+    /// #     }
+    /// # }
+    /// # struct ConsoleLogger;
+    /// # impl Log for ConsoleLogger {
+    /// #     fn enabled(&self, _: &Metadata) -> bool { true }
+    /// #     fn log(&self, record: &Record) { eprintln!("{}", record.args()) }
+    /// #     fn flush(&self) {}
+    /// # }
+    /// /// Forwards every record to all loggers that are enabled for it.
+    /// struct Multiplexer(Vec<Box<dyn Log>>);
+    ///
+    /// impl Log for Multiplexer {
+    ///     fn enabled(&self, metadata: &Metadata) -> bool {
+    ///         self.0.iter().any(|logger| logger.enabled(metadata))
+    ///     }
+    ///
+    ///     fn log(&self, record: &Record) {
+    ///         for logger in self.0.iter().filter(|logger| logger.enabled(record.metadata())) {
+    ///             logger.log(record);
+    ///         }
+    ///     }
+    ///
+    ///     fn flush(&self) {
+    ///         self.0.iter().for_each(|logger| logger.flush());
+    ///     }
+    /// }
+    ///
+    /// let connection = redis::Client::open("redis://127.0.0.1/").unwrap().get_connection().unwrap();
+    /// let config = RedisLoggerConfigBuilder::build_with_pubsub(connection, vec!["logging".into()], MessageEncoder);
+    /// let redis_logger = RedisLogger::boxed(LevelFilter::Info, config);
+    ///
+    /// log::set_max_level(LevelFilter::Info);
+    /// log::set_logger(Box::leak(Box::new(Multiplexer(vec![redis_logger, Box::new(ConsoleLogger)])))).unwrap();
+    /// ```
+    ///
+    /// `init_with` does the same with a multiplexer provided by this crate.
+    pub fn boxed(level: LevelFilter, config: RedisLoggerConfig<CONN, PUBSUB, STREAM>) -> Box<Self> {
+        Self::new(level, config)
+    }
+
+    /// Initializes the Redis logger with the specified log level and configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - The log level to set for the logger.
+    /// * `config` - The configuration for the Redis logger.
+    ///
+    /// # Returns
+    ///
+    /// Result indicating success or an error of type `RedisLoggerConfigError`.
+    /// If successful, the logger is set as the global logger.
+    ///
+    /// # Errors
+    ///
+    /// see above
+    pub fn init(level: LevelFilter, config: RedisLoggerConfig<CONN, PUBSUB, STREAM>) -> Result<(), SetLoggerError> {
+        let redis_logger = Self::new(level, config);
+        log::set_max_level(level);
+        log::set_boxed_logger(redis_logger)?;
+        Ok(())
+    }
+
+    /// Initializes the Redis logger together with other loggers, e.g. a console or file logger, as the global logger.
+    ///
+    /// Every record is forwarded to each of the loggers, the Redis logger first, that is enabled for it, and flushing
+    /// flushes all of them. `level` is the global max level as well as the level of the Redis logger, so records above
+    /// it reach none of the loggers; the other loggers can filter further in their `enabled`. To combine loggers in
+    /// another way, build the Redis logger with `boxed` and register it yourself.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if another logger has already been set as the global logger.
+    pub fn init_with(
+        level: LevelFilter,
+        config: RedisLoggerConfig<CONN, PUBSUB, STREAM>,
+        others: Vec<Box<dyn Log>>,
+    ) -> Result<(), SetLoggerError> {
+        let mut loggers: Vec<Box<dyn Log>> = vec![Self::new(level, config)];
+        loggers.extend(others);
+        log::set_max_level(level);
+        log::set_boxed_logger(Box::new(Multiplexer(loggers)))
+    }
+
+    /// Sets this logger as the global logger without boxing it again, with the specified log level.
+    ///
+    /// `log::set_logger` requires a logger living for the rest of the program, so the logger must be `'static`, e.g. kept
+    /// in a `static` `OnceLock` or leaked with `Box::leak`. It can't be built in a `const` context, as the configuration
+    /// holds the connection and encoders created at runtime. The level replaces the one the logger was created with.
+    ///
+    /// ```rust,no_run
+    /// # use std::sync::OnceLock;
+    /// # use log::LevelFilter;
+    /// # use redis_logger::{DummyStreamEncoder, PubSubEncoder, RedisLogger, RedisLoggerConfigBuilder};
+    /// # struct MessageEncoder;
+    /// # impl PubSubEncoder for MessageEncoder {
+    /// #     fn encode(&self, record: &log::Record) -> Vec<u8> {
+    /// #         record.args().to_string().into_bytes()
+    /// #     }
+    /// # }
+    /// static LOGGER: OnceLock<Box<RedisLogger<redis::Connection, MessageEncoder, DummyStreamEncoder>>> = OnceLock::new();
+    ///
+    /// let connection = redis::Client::open("redis://127.0.0.1/").unwrap().get_connection().unwrap();
+    /// let config = RedisLoggerConfigBuilder::build_with_pubsub(connection, vec!["logging".into()], MessageEncoder);
+    /// LOGGER
+    ///     .get_or_init(|| RedisLogger::new(LevelFilter::Info, config))
+    ///     .init_static(LevelFilter::Info)
+    ///     .unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if another logger has already been set as the global logger.
+    pub fn init_static(&'static self, level: LevelFilter) -> Result<(), SetLoggerError> {
+        self.level.set(level);
+        log::set_max_level(level);
+        log::set_logger(self)
+    }
+
+    /// Takes all records out of the retry buffer (see `RedisLoggerConfig::with_retry_buffer`), leaving it empty.
+    ///
+    /// Use this on shutdown or for diagnostics to persist records that could not be written to Redis yet.
+    /// Returns an empty vector if no retry buffer is configured.
+    pub fn take_buffered(&self) -> Vec<BufferedRecord> {
+        self.config().take_buffered()
+    }
+
+    /// Replaces the connection with a new one created by the function set with `RedisLoggerConfig::with_connect`,
+    /// e.g. after a planned failover. Records logged concurrently are written to the old or the new connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of creating the new connection, in which case the old one is kept, or an error of kind
+    /// `ClientError` if no function to connect is configured.
+    ///
+    /// A successful reconnect enables a logger disabled by `RedisLoggerConfig::with_max_total_retries` again.
+    pub fn reconnect(&self) -> redis::RedisResult<()> {
+        let connection = self.config().new_connection()?;
+        self.set_connection(connection);
+        Ok(())
+    }
+
+    /// Replaces the connection with one provided by the caller, for applications that manage the lifecycle of their
+    /// connections themselves, e.g. in a health check loop, instead of letting the logger reconnect. The connection is
+    /// swapped while holding its lock, so records logged concurrently are written completely to the old or the new one.
+    ///
+    /// The connection is used as it is, e.g. the client name set with `RedisLoggerConfig::with_client_name` is not set
+    /// on it. Like `reconnect`, it enables a logger disabled by `RedisLoggerConfig::with_max_total_retries` again.
+    pub fn set_connection(&self, connection: CONN) {
+        let config = self.config();
+        *config.connection.lock().unwrap() = connection;
+        if let Some(budget) = &config.retry_budget {
+            budget.failures.store(0, Ordering::Relaxed);
+            budget.disabled.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns `true` if a record with the given metadata would be written to at least one channel or stream.
+    ///
+    /// In addition to the level checked by `enabled`, this considers the routing of the record: with
+    /// `RedisLoggerConfig::with_level_streams`, a level may be routed to no stream at all, so the record is dropped
+    /// if no pub/sub channels are configured either.
+    ///
+    /// The `log` macros only call `enabled`, so this is meant to be called manually to skip building expensive
+    /// log messages that would be dropped anyway.
+    pub fn would_log(&self, metadata: &Metadata) -> bool {
+        self.enabled(metadata) && self.config().has_targets(metadata.level())
+    }
+
+    /// Returns what the logger is connected to and how many channels and streams it writes to, e.g. for a diagnostics
+    /// endpoint. The connection details are only known if set with `RedisLoggerConfig::with_connection_info`, which
+    /// `RedisLogger::simple` and `RedisLoggerFileConfig` do. Secrets like the password are never included.
+    pub fn connection_info(&self) -> LoggerConnectionInfo {
+        let config = self.config();
+        let mut info = config.connection_info.clone().unwrap_or(LoggerConnectionInfo {
+            host: None,
+            port: None,
+            db: None,
+            tls: false,
+            username: None,
+            has_password: false,
+            channels: 0,
+            streams: 0,
+        });
+        let channels = config.channels.iter().map(|(channels, _)| channels.len());
+        info.channels = channels
+            .chain(config.channel_encoders.iter().map(|(channels, _)| channels.len()))
+            .chain(config.alert_channel.iter().map(|_| 1))
+            .sum();
+        let streams = config.streams.iter().map(|(streams, _)| streams.len());
+        info.streams = streams
+            .chain(config.multi_streams.iter().map(|(streams, _)| streams.len()))
+            .sum::<usize>()
+            + config.shared_streams.len();
+        info
+    }
+
+    /// Returns how many records were passed to `log`, how many of them were skipped due to their level or target and how
+    /// many were sent to Redis, e.g. to tune verbosity or to spot a misconfigured level that skips everything. The
+    /// counts cover the lifetime of the logger, including reloads. The `log` macros skip records above the global max
+    /// level before calling the logger, so those are not seen.
+    pub fn stats(&self) -> LoggerStats {
+        LoggerStats {
+            seen: self.counters.seen.load(Ordering::Relaxed),
+            skipped: self.counters.skipped.load(Ordering::Relaxed),
+            emitted: self.counters.emitted.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the most recent error writing to Redis or reconnecting, prefixed with the time it occurred in milliseconds
+    /// since the Unix epoch, e.g. `1718000000000: Error logging to Redis (retryable): ...`.
+    ///
+    /// The error is cleared once commands are written successfully again, so a health check can poll this and report the
+    /// logger as unhealthy while it returns `Some`.
+    pub fn last_error(&self) -> Option<String> {
+        self.config().last_error.lock().unwrap().as_ref().map(|(time, error)| {
+            let millis = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+            format!("{millis}: {error}")
+        })
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "default_encoders")))]
+#[cfg(feature = "default_encoders")]
+impl RedisLogger<redis::Connection, DefaultPubSubEncoder, DummyStreamEncoder> {
+    /// Connects to the Redis server at `url` and initializes a logger publishing to `channel` with the
+    /// `DefaultPubSubEncoder` as the global logger. Meant for quick scripts, use `RedisLoggerConfigBuilder` and
+    /// `RedisLogger::init` for everything else.
+    ///
+    /// ```no_run
+    /// use log::LevelFilter;
+    /// use redis_logger::RedisLogger;
+    ///
+    /// RedisLogger::simple("redis://127.0.0.1/", "logging", LevelFilter::Info).unwrap();
+    /// log::info!("Hello Redis");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL is invalid, Redis is not reachable or a global logger has already been set.
+    pub fn simple(url: &str, channel: &str, level: LevelFilter) -> Result<(), InitError> {
+        let client = redis::Client::open(url)?;
+        let connection = client.get_connection()?;
+        Self::init(
+            level,
+            RedisLoggerConfigBuilder::build_with_pubsub_default(connection, vec![channel.to_owned()])
+                .with_connection_info(client.get_connection_info()),
+        )?;
+        Ok(())
+    }
+}
+
+impl<CONN, PUBSUB, STREAM> fmt::Debug for RedisLogger<CONN, PUBSUB, STREAM>
+where
+    CONN: ConnectionLike + Send + Sync,
+    PUBSUB: PubSubEncoder + fmt::Debug,
+    STREAM: StreamEncoder + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisLogger")
+            .field("level", &self.level.get())
+            .field("config", &*self.config())
+            .finish()
+    }
+}
+
+impl<CONN, PUBSUB, STREAM> RedisLogger<CONN, PUBSUB, STREAM>
+where
+    CONN: ConnectionLike + Send + Sync,
+    PUBSUB: PubSubEncoder,
+    STREAM: StreamEncoder,
+{
+    /// Returns the current configuration. A `reload` replaces it for later calls, while the returned one stays usable.
+    fn config(&self) -> Arc<RedisLoggerConfig<CONN, PUBSUB, STREAM>> {
+        // this unwrap only panics if the lock is poisoned, so we can't do much anyway and will panic, too!
+        Arc::clone(&self.config.read().unwrap())
+    }
+}
+
+/// Implements the `Log` trait for the `RedisLogger` struct.
+///
+/// This implementation provides the necessary methods to enable logging to Redis.
+/// The `enabled` method checks if the log level of the provided `Metadata` is less than or equal to the configured log level.
+/// The `log` method publishes log messages to Redis channels and streams based on the configuration in one atomic operation using a pipeline,
+/// or with one query per channel and stream if independent targets are configured.
+/// The `flush` method is a no-op in this implementation.
+impl<CONN, PUBSUB, STREAM> Log for RedisLogger<CONN, PUBSUB, STREAM>
+where
+    CONN: ConnectionLike + Send + Sync,
+    PUBSUB: PubSubEncoder,
+    STREAM: StreamEncoder,
+{
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level.get() && !self.config().is_ignored_target(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        // `enabled` is also called by users and `log_enabled!`, so records are only counted here
+        self.counters.seen.fetch_add(1, Ordering::Relaxed);
+        // the configuration is taken once for the whole record, so a reload never splits a record between two configurations
+        let config = self.config();
+        if record.level() > self.level.get() || config.is_ignored_target(record.target()) {
+            self.counters.skipped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        if config.has_targets(record.level()) && !config.is_disabled() {
+            // sampling decides for the whole record, never per target, so the targets stay consistent
+            if !config.is_sampled(record.target()) {
+                config.count_dropped(DropReason::Sampling, 1);
+                return;
+            }
+            if config.is_shed(record.level()) {
+                config.count_dropped(DropReason::Shedding, 1);
+                return;
+            }
+            if config.is_duplicate(record) {
+                config.count_dropped(DropReason::Duplicate, 1);
+                return;
+            }
+            config.write_drop_markers();
+            self.counters.emitted.fetch_add(1, Ordering::Relaxed);
+            if let Some(heartbeat) = &config.idle_heartbeat {
+                heartbeat.touch();
+            }
+            let mut fallback = None;
+            let commands = config.commands(record, &mut fallback);
+            if !config.send(&commands) {
+                config.write_fallback(fallback);
+            }
+        }
+    }
+
+    /// Sends the records kept in the retry buffer (see `RedisLoggerConfig::with_retry_buffer`), if any.
+    fn flush(&self) {
+        self.config().flush();
+    }
+}
+
+/// What a logger is connected to, without secrets, as returned by `RedisLogger::connection_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LoggerConnectionInfo {
+    /// The host, or the path of the Unix socket. `None` if not set with `RedisLoggerConfig::with_connection_info`,
+    /// like the other connection details.
+    pub host: Option<String>,
+    /// The port, `None` for a Unix socket.
+    pub port: Option<u16>,
+    /// The database index.
+    pub db: Option<i64>,
+    /// Whether the connection uses TLS.
+    pub tls: bool,
+    /// The username, if any.
+    pub username: Option<String>,
+    /// Whether a password is used. The password itself is never kept.
+    pub has_password: bool,
+    /// The number of pub/sub channels the logger publishes to, not counting channels chosen per record.
+    pub channels: usize,
+    /// The number of streams the logger writes to, not counting streams chosen per record.
+    pub streams: usize,
+}
+
+/// A command the logger sends, or would send in a dry run, as passed to the observer set with
+/// `RedisLoggerConfig::with_command_observer`. Its `Display` output is the command as typed in `redis-cli`, e.g.
+/// `PUBLISH "logging" "Started"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ObservedCommand {
+    /// The channel or stream the command writes to.
+    pub target: String,
+    /// The name of the command, e.g. `PUBLISH` or `XADD`.
+    pub name: String,
+    /// The arguments following the name, e.g. the channel and the encoded record of a `PUBLISH`.
+    pub args: Vec<Vec<u8>>,
+}
+
+impl ObservedCommand {
+    fn new((target, command): &Command<'_>) -> Self {
+        let mut args = command.args_iter().filter_map(|arg| match arg {
+            redis::Arg::Simple(arg) => Some(arg.to_vec()),
+            redis::Arg::Cursor => None,
+        });
+        let name = args
+            .next()
+            .map(|name| String::from_utf8_lossy(&name).into_owned())
+            .unwrap_or_default();
+        Self {
+            target: target.to_string(),
+            name,
+            args: args.collect(),
+        }
+    }
+}
+
+impl fmt::Display for ObservedCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        for arg in &self.args {
+            write!(f, " {:?}", String::from_utf8_lossy(arg))?;
+        }
+        Ok(())
+    }
+}
+
+/// A command that could not be written to Redis and was kept in the retry buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BufferedRecord {
+    /// The channel or stream the command writes to.
+    pub target: String,
+    /// The complete `PUBLISH` or `XADD` command including the encoded record, serialized in the Redis protocol (RESP).
+    pub command: Vec<u8>,
+    /// When the command was buffered.
+    pub timestamp: SystemTime,
+}
+
+/// A stream whose name is derived from the current date.
+#[cfg(feature = "dated_streams")]
+struct DatedStream {
+    base: String,
+    format: String,
+    now: Box<dyn Fn() -> chrono::DateTime<chrono::Utc> + Send + Sync>,
+}
+
+#[cfg(feature = "dated_streams")]
+impl DatedStream {
+    fn name(&self) -> String {
+        format!("{}{}", self.base, (self.now)().format(&self.format))
+    }
+}
+
+#[cfg(feature = "dated_streams")]
+impl fmt::Debug for DatedStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatedStream")
+            .field("base", &self.base)
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+/// How streams are trimmed when adding entries.
+#[derive(Debug)]
+struct StreamTrim {
+    maxlen: Option<usize>,
+    min_age: Option<Duration>,
+    limit: Option<usize>,
+    interval: u64,
+    records: AtomicU64,
+    no_mkstream: bool,
+}
+
+impl Default for StreamTrim {
+    fn default() -> Self {
+        Self {
+            maxlen: None,
+            min_age: None,
+            limit: None,
+            interval: 100,
+            records: AtomicU64::new(0),
+            no_mkstream: false,
+        }
+    }
+}
+
+impl StreamTrim {
+    /// Creates an `XADD` with the entry id (`*` for an automatic one) and the trim strategy. `MAXLEN` takes precedence
+    /// over `MINID`.
+    fn xadd(&self, stream: &str, id: &str, fields: &[(String, Vec<u8>)]) -> redis::Cmd {
+        // sized up front, so adding many fields doesn't grow the command step by step; the 7 arguments and 64 bytes cover
+        // the command name and the trim strategy
+        let size = fields.iter().map(|(field, value)| field.len() + value.len()).sum::<usize>();
+        let mut xadd = redis::Cmd::with_capacity(7 + 2 * fields.len(), 64 + stream.len() + id.len() + size);
+        xadd.arg("XADD").arg(stream);
+        if self.no_mkstream {
+            xadd.arg("NOMKSTREAM");
+        }
+        if let Some(maxlen) = self.maxlen {
+            xadd.arg("MAXLEN").arg("~").arg(maxlen);
+            self.add_limit(&mut xadd);
+        } else if let Some(min_age) = self.min_age {
+            xadd.arg("MINID").arg("~").arg(Self::min_id(min_age));
+            self.add_limit(&mut xadd);
+        }
+        xadd.arg(id).arg(fields);
+        xadd
+    }
+
+    /// Creates an `XTRIM` applying the trim strategy once, e.g. to a stream created by `provision_streams`. `MAXLEN`
+    /// takes precedence over `MINID`, like in `xadd`.
+    fn retention(&self, stream: &str) -> Option<redis::Cmd> {
+        if let Some(maxlen) = self.maxlen {
+            let mut xtrim = redis::cmd("XTRIM");
+            xtrim.arg(stream).arg("MAXLEN").arg("~").arg(maxlen);
+            self.add_limit(&mut xtrim);
+            Some(xtrim)
+        } else {
+            self.min_age.map(|min_age| self.xtrim(stream, &Self::min_id(min_age)))
+        }
+    }
+
+    /// Creates an `XTRIM` removing the entries older than `min_id`.
+    fn xtrim(&self, stream: &str, min_id: &str) -> redis::Cmd {
+        let mut xtrim = redis::cmd("XTRIM");
+        xtrim.arg(stream).arg("MINID").arg("~").arg(min_id);
+        self.add_limit(&mut xtrim);
+        xtrim
+    }
+
+    /// Adds the `LIMIT` clause, which must follow the approximate trim strategy.
+    fn add_limit(&self, command: &mut redis::Cmd) {
+        if let Some(limit) = self.limit {
+            command.arg("LIMIT").arg(limit);
+        }
+    }
+
+    /// Returns the `MINID` for an additional `XTRIM` if both strategies are configured and the `XTRIM` is due for this record.
+    fn due_min_id(&self) -> Option<String> {
+        match (self.maxlen, self.min_age) {
+            (Some(_), Some(min_age)) if (self.records.fetch_add(1, Ordering::Relaxed) + 1) % self.interval == 0 => {
+                Some(Self::min_id(min_age))
+            }
+            _ => None,
+        }
+    }
+
+    fn min_id(min_age: Duration) -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        now.saturating_sub(min_age).as_millis().to_string()
+    }
+}
+
+/// Generates stream entry ids of the form `<epoch_ms>-<seq>` that strictly increase within the process, even if the
+/// system clock goes backwards.
+#[derive(Debug, Default)]
+struct StreamIds {
+    last: Mutex<(u64, u64)>,
+}
+
+impl StreamIds {
+    fn next(&self) -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.next_at(u64::try_from(now.as_millis()).unwrap_or(u64::MAX))
+    }
+
+    /// Returns the next id for the time `millis`, continuing the sequence of the last id if `millis` is not later.
+    fn next_at(&self, millis: u64) -> String {
+        let mut last = self.last.lock().unwrap();
+        *last = if millis > last.0 { (millis, 0) } else { (last.0, last.1 + 1) };
+        format!("{}-{}", last.0, last.1)
+    }
+}
+
+/// Sends the packed commands without reading the replies, then reads the reply to every command on its own, so an
+/// error reply only fails its command. Fails as a whole if the commands can't be sent or the connection fails.
+///
+/// See `with_per_command_results` for the connections this works with.
+fn req_per_command<CONN: ConnectionLike>(
+    connection: &mut CONN,
+    packed: &[u8],
+    count: usize,
+) -> redis::RedisResult<Vec<redis::RedisResult<redis::Value>>> {
+    connection.req_packed_commands(packed, 0, 0)?;
+    let mut replies = Vec::with_capacity(count);
+    for _ in 0..count {
+        match connection.req_packed_commands(&[], 0, 1) {
+            Ok(mut reply) => replies.push(Ok(reply.pop().unwrap_or(redis::Value::Nil))),
+            Err(error) if error.is_io_error() || !connection.is_open() => return Err(error),
+            Err(error) => replies.push(Err(error)),
+        }
+    }
+    Ok(replies)
+}
+
+fn spool_error(spool: &DiskSpool, error: io::Error) -> RedisLoggerError {
+    RedisLoggerError::Spool {
+        path: spool.path().to_owned(),
+        error,
+    }
+}
+
+/// Returns `true` if an `XADD` was rejected because its id is not greater than the id of the last entry in the stream.
+fn is_stream_id_rejected(error: &RedisError) -> bool {
+    error.kind() == ErrorKind::ResponseError
+        && error
+            .detail()
+            .is_some_and(|detail| detail.contains("equal or smaller than the target stream top item"))
+}
+
+/// Returns the `XADD` commands of `commands` with their explicit ids replaced by `*`, or `None` if there are none.
+fn with_auto_stream_ids<'a>(commands: &[Command<'a>]) -> Option<Vec<Command<'a>>> {
+    let is_id = |arg: &[u8]| {
+        let mut parts = arg.splitn(2, |&byte| byte == b'-');
+        let mut is_number = || {
+            parts
+                .next()
+                .is_some_and(|part| !part.is_empty() && part.iter().all(u8::is_ascii_digit))
+        };
+        is_number() && is_number()
+    };
+    let mut fallback = Vec::new();
+    for (stream, command) in commands {
+        let args: Vec<&[u8]> = command
+            .args_iter()
+            .filter_map(|arg| match arg {
+                redis::Arg::Simple(arg) => Some(arg),
+                redis::Arg::Cursor => None,
+            })
+            .collect();
+        if args.first() != Some(&&b"XADD"[..]) {
+            continue;
+        }
+        // the id is the first argument after the stream name looking like one, as the trim clauses never contain a `-`
+        let Some(id_index) = args.iter().skip(2).position(|arg| is_id(arg)).map(|index| index + 2) else {
+            continue;
+        };
+        let mut xadd = redis::cmd("XADD");
+        for (index, arg) in args.iter().enumerate().skip(1) {
+            if index == id_index {
+                xadd.arg("*");
+            } else {
+                xadd.arg(*arg);
+            }
+        }
+        fallback.push((stream.clone(), xadd));
+    }
+    (!fallback.is_empty()).then_some(fallback)
+}
+
+/// Writes records to stderr as long as no write to Redis has succeeded.
+struct StderrFallback {
+    connected: AtomicBool,
+    writer: Mutex<Box<dyn io::Write + Send>>,
+}
+
+impl fmt::Debug for StderrFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StderrFallback")
+            .field("connected", &self.connected)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct RetryBuffer {
+    capacity: usize,
+    records: Mutex<VecDeque<BufferedRecord>>,
+    /// The occupancy in percent above which records of the level or less severe ones are dropped, see
+    /// `RedisLoggerConfig::with_adaptive_shedding`.
+    shedding: Option<(u8, Level)>,
+}
+
+/// A command to send to Redis together with the channel or stream it writes to.
+type Command<'a> = (Cow<'a, str>, redis::Cmd);
+
+/// Returns `true` if `target` is `parent` or one of its submodules, e.g. `myapp::poller` for the parent `myapp`.
+fn is_in_target(target: &str, parent: &str) -> bool {
+    target
+        .strip_prefix(parent)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// Keeps one of every `rate` records of the targets starting with `prefix`.
+#[derive(Debug)]
+struct TargetSampling {
+    prefix: String,
+    rate: u64,
+    records: AtomicU64,
+}
+
+/// Why records or commands were dropped, reported by the markers of `RedisLoggerConfig::with_drop_markers`.
+#[derive(Debug, Clone, Copy)]
+enum DropReason {
+    Sampling,
+    RetryBufferFull,
+    SpoolFailed,
+    EncoderPanic,
+    Duplicate,
+    Shedding,
+}
+
+impl DropReason {
+    const ALL: [Self; 6] = [
+        Self::Sampling,
+        Self::RetryBufferFull,
+        Self::SpoolFailed,
+        Self::EncoderPanic,
+        Self::Duplicate,
+        Self::Shedding,
+    ];
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Sampling => "sampling",
+            Self::RetryBufferFull => "retry_buffer_full",
+            Self::SpoolFailed => "spool_failed",
+            Self::EncoderPanic => "encoder_panic",
+            Self::Duplicate => "duplicate",
+            Self::Shedding => "shedding",
+        }
+    }
+}
+
+/// Limits the reported errors to one per interval, see `RedisLoggerConfig::with_error_throttle`.
+#[derive(Debug)]
+struct ErrorThrottle {
+    interval: Duration,
+    /// When the last error was reported and how many errors were suppressed since.
+    state: Mutex<(Option<Instant>, u64)>,
+}
+
+/// Counts failed writes since the last successful one, see `RedisLoggerConfig::with_max_total_retries`.
+#[derive(Debug)]
+struct RetryBudget {
+    max: u64,
+    failures: AtomicU64,
+    disabled: AtomicBool,
+}
+
+type QueueEncoder = Box<dyn Fn(&Record) -> Vec<u8> + Send + Sync>;
+
+/// A list records are pushed to for consumption by another process, see `RedisLoggerConfig::with_queue`.
+struct Queue {
+    list: String,
+    encoder: QueueEncoder,
+}
+
+impl fmt::Debug for Queue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue").field("list", &self.list).finish_non_exhaustive()
+    }
+}
+
+/// A command of a Redis module records are written with, see `RedisLoggerConfig::with_custom_command`.
+struct CustomCommand {
+    name: String,
+    keys: Vec<String>,
+    encoder: Box<dyn StreamEncoder>,
+}
+
+impl fmt::Debug for CustomCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomCommand")
+            .field("name", &self.name)
+            .field("keys", &self.keys)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Counts drops by reason until the next marker is written.
+#[derive(Debug)]
+struct DropMarkers {
+    interval: Duration,
+    last: Mutex<Instant>,
+    dropped: [AtomicU64; DropReason::ALL.len()],
+}
+
+/// Tracks when the last record was written, see `RedisLoggerConfig::with_idle_heartbeat`.
+#[derive(Debug)]
+struct IdleHeartbeat {
+    interval: Duration,
+    start: Instant,
+    /// When the last record or heartbeat was written, in milliseconds since `start`.
+    last_entry: Arc<AtomicU64>,
+}
+
+impl IdleHeartbeat {
+    fn touch(&self) {
+        let elapsed = u64::try_from(self.start.elapsed().as_millis()).unwrap_or(u64::MAX);
+        self.last_entry.store(elapsed, Ordering::Relaxed);
+    }
+
+    /// Returns how long no record or heartbeat has been written.
+    fn idle(&self) -> Duration {
+        self.start
+            .elapsed()
+            .saturating_sub(Duration::from_millis(self.last_entry.load(Ordering::Relaxed)))
+    }
+}
+
+/// How often `RedisLoggerConfig::with_warn_on_no_subscribers` reports channels without subscribers at most.
+const NO_SUBSCRIBERS_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The stream field holding the pub/sub message in streams configured with
+/// `RedisLoggerConfigBuilder::build_with_pubsub_and_stream_shared`.
+const SHARED_STREAM_FIELD: &str = "message";
+
+/// Publishes `ARGV[1]` to the channels `ARGV[2..]` and returns the total number of receivers, see
+/// `RedisLoggerConfig::with_scripted_fan_out`.
+const FAN_OUT_SCRIPT: &str =
+    "local receivers = 0 for i = 2, #ARGV do receivers = receivers + redis.call('PUBLISH', ARGV[i], ARGV[1]) end return receivers";
+
+/// Streams written with their own encoder, see `RedisLoggerConfig::with_multi_stream`.
+type MultiStream = (Vec<String>, Box<dyn StreamEncoder>);
+
+/// Channels published to with their own encoder, see `RedisLoggerConfig::with_channel_encoders`.
+type ChannelEncoders = (Vec<String>, Box<dyn PubSubEncoder>);
+
+/// Selects the channels of a record, see `RedisLoggerConfig::with_dynamic_channels`.
+type DynamicChannels = Box<dyn Fn(&Record) -> Vec<String> + Send + Sync>;
+
+/// Observes the commands written for records, see `RedisLoggerConfig::with_command_observer`.
+type CommandObserver = Box<dyn Fn(&ObservedCommand) + Send + Sync>;
+
+/// Handler for errors that occur while logging to Redis.
+pub type ErrorHandler = Arc<dyn Fn(&RedisLoggerError) + Send + Sync>;
+
+/// Configuration for the Redis logger. Pass to `RedisLogger` to configure the logger.
+///
+/// Created by one of the `RedisLoggerConfigBuilder` methods. Optional settings are applied with the `with_*` methods.
+pub struct RedisLoggerConfig<CONN, PUBSUB, STREAM>
+where
+    CONN: ConnectionLike + Send + Sync,
+    PUBSUB: PubSubEncoder,
+    STREAM: StreamEncoder,
+{
+    connection: Arc<Mutex<CONN>>,
+    channels: Option<(Vec<String>, PUBSUB)>,
+    streams: Option<(Vec<String>, STREAM)>,
+    max_retries: u32,
+    error_handler: Option<ErrorHandler>,
+    independent_targets: bool,
+    per_command_results: bool,
+    retry_buffer: Option<RetryBuffer>,
+    wait_replicas: Option<(usize, Duration)>,
+    key_prefix: String,
+    #[cfg(feature = "dated_streams")]
+    dated_stream: Option<DatedStream>,
+    level_streams: HashMap<Level, Vec<String>>,
+    stream_trim: StreamTrim,
+    background_trim: Option<(usize, Duration)>,
+    idle_heartbeat: Option<IdleHeartbeat>,
+    max_pipeline_commands: usize,
+    remote_level: Option<(String, Duration)>,
+    stderr_fallback: Option<StderrFallback>,
+    shared_streams: Vec<String>,
+    ignored_targets: Vec<String>,
+    disk_spool: Option<DiskSpool>,
+    connect: Option<Box<dyn Fn() -> redis::RedisResult<CONN> + Send + Sync>>,
+    client_name: Option<String>,
+    connection_info: Option<LoggerConnectionInfo>,
+    target_sampling: Vec<TargetSampling>,
+    dedup_ttl: Option<Duration>,
+    record_hasher: Box<dyn RecordHasher>,
+    sharded_channels: bool,
+    drop_markers: Option<DropMarkers>,
+    stream_ids: Option<StreamIds>,
+    routing_key: Option<String>,
+    retry_budget: Option<RetryBudget>,
+    size_observer: Option<Box<dyn Fn(usize) + Send + Sync>>,
+    command_observer: Option<CommandObserver>,
+    dry_run: bool,
+    queues: Vec<Queue>,
+    custom_commands: Vec<CustomCommand>,
+    multi_streams: Vec<MultiStream>,
+    channel_encoders: Vec<ChannelEncoders>,
+    dynamic_channels: Option<DynamicChannels>,
+    alert_channel: Option<(String, Level)>,
+    scripted_fan_out: bool,
+    #[cfg(feature = "compression")]
+    compressed_channel: Option<(String, Compression)>,
+    no_subscribers_warning: Option<Mutex<Option<Instant>>>,
+    error_throttle: Option<ErrorThrottle>,
+    last_error: Mutex<Option<(SystemTime, String)>>,
+    #[cfg(feature = "time_series")]
+    time_series: Vec<TimeSeriesTarget>,
+}
+
+impl<CONN, PUBSUB, STREAM> RedisLoggerConfig<CONN, PUBSUB, STREAM>
+where
+    CONN: ConnectionLike + Send + Sync,
+    PUBSUB: PubSubEncoder,
+    STREAM: StreamEncoder,
+{
+    fn new(connection: CONN, channels: Option<(Vec<String>, PUBSUB)>, streams: Option<(Vec<String>, STREAM)>) -> Self {
+        LIVE_CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+        Self {
+            connection: Arc::new(Mutex::new(connection)),
+            channels,
+            streams,
+            max_retries: 0,
+            error_handler: None,
+            independent_targets: false,
+            per_command_results: false,
+            retry_buffer: None,
+            wait_replicas: None,
+            key_prefix: String::new(),
+            #[cfg(feature = "dated_streams")]
+            dated_stream: None,
+            level_streams: HashMap::new(),
+            stream_trim: StreamTrim::default(),
+            background_trim: None,
+            idle_heartbeat: None,
+            max_pipeline_commands: usize::MAX,
+            remote_level: None,
+            stderr_fallback: None,
+            shared_streams: Vec::new(),
+            ignored_targets: vec!["redis".to_owned()],
+            disk_spool: None,
+            connect: None,
+            client_name: None,
+            connection_info: None,
+            target_sampling: Vec::new(),
+            dedup_ttl: None,
+            record_hasher: Box::new(DefaultRecordHasher::new()),
+            sharded_channels: false,
+            drop_markers: None,
+            stream_ids: None,
+            routing_key: None,
+            retry_budget: None,
+            size_observer: None,
+            command_observer: None,
+            dry_run: false,
+            queues: Vec::new(),
+            custom_commands: Vec::new(),
+            multi_streams: Vec::new(),
+            channel_encoders: Vec::new(),
+            dynamic_channels: None,
+            alert_channel: None,
+            scripted_fan_out: false,
+            #[cfg(feature = "compression")]
+            compressed_channel: None,
+            no_subscribers_warning: None,
+            error_throttle: None,
+            last_error: Mutex::new(None),
+            #[cfg(feature = "time_series")]
+            time_series: Vec::new(),
+        }
+    }
+
+    /// Sets how often a failed write is retried. Only errors classified as transient by `is_retryable` are retried,
+    /// permanent errors are reported immediately. Defaults to 0, i.e. no retries.
+    #[must_use]
+    pub const fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Disables the logger after `max` failed attempts to write in a row, including retries, so it stops trying once
+    /// Redis is gone for good, e.g. in ephemeral environments. Every successful write resets the count.
+    ///
+    /// Disabling is reported as `RedisLoggerError::Disabled` to the error handler. A disabled logger drops all records
+    /// without encoding them and doesn't replay buffered ones on `flush`, until `RedisLogger::reconnect` succeeds.
+    /// A value of 0 is treated as 1. Defaults to no limit.
+    #[must_use]
+    pub fn with_max_total_retries(mut self, max: u64) -> Self {
+        self.retry_budget = Some(RetryBudget {
+            max,
+            failures: AtomicU64::new(0),
+            disabled: AtomicBool::new(false),
+        });
+        self
+    }
+
+    /// Sets an observer that is called with the size in bytes of every encoded record, e.g. to feed a histogram for
+    /// planning Redis memory and `MAXLEN`s. It is called once for the pub/sub message and once for the stream fields,
+    /// whose size is the total length of all field names and values. Keep it cheap, as it runs for every record.
+    #[must_use]
+    pub fn with_size_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.size_observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Sets an observer that is called with every command written for a record, before it is sent, e.g. to verify the
+    /// routing and encoding of a configuration while debugging. Keep it cheap, as it runs for every command.
+    #[must_use]
+    pub fn with_command_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&ObservedCommand) + Send + Sync + 'static,
+    {
+        self.command_observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Builds the commands for every record but doesn't send them to Redis. Instead, they are passed to the observer set
+    /// with `with_command_observer`, or printed to stderr if none is set, so channels, streams and encoders can be
+    /// checked without a Redis server. This includes the `SET` of `with_redis_dedup`, so no record is claimed and none is
+    /// suppressed as a duplicate. Commands not written for a record, e.g. setting the client name or those of
+    /// `with_background_trim`, are still sent to the connection. Defaults to `false`.
+    #[must_use]
+    pub const fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Sets a handler that is called with every error that occurs while logging to Redis.
+    /// Without a handler, errors are printed to stderr.
+    #[must_use]
+    pub fn with_error_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&RedisLoggerError) + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Reports at most one error per `interval` to the error handler (or stderr), so an unreachable Redis doesn't flood
+    /// whatever the handler writes to. Errors within the interval are counted, and the next reported error is wrapped in
+    /// `RedisLoggerError::Throttled` with the number of suppressed errors. `RedisLogger::last_error` is not throttled.
+    /// Defaults to reporting every error.
+    #[must_use]
+    pub fn with_error_throttle(mut self, interval: Duration) -> Self {
+        self.error_throttle = Some(ErrorThrottle {
+            interval,
+            state: Mutex::new((None, 0)),
+        });
+        self
+    }
+
+    /// Prepends `prefix` to the names of all pub/sub channels and streams, e.g. to namespace them by environment (`prod:`).
+    ///
+    /// The names are changed right away, so `"logging"` becomes `"prod:logging"` with the prefix `"prod:"`.
+    /// Names configured later, like a dated stream, get the prefix as well.
+    /// Calling this method multiple times prepends each prefix in turn.
+    #[must_use]
+    pub fn with_key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        let channels = self.channels.iter_mut().flat_map(|(channels, _)| channels.iter_mut());
+        let streams = self.streams.iter_mut().flat_map(|(streams, _)| streams.iter_mut());
+        let queues = self.queues.iter_mut().map(|queue| &mut queue.list);
+        let multi_streams = self.multi_streams.iter_mut().flat_map(|(streams, _)| streams.iter_mut());
+        let custom_keys = self.custom_commands.iter_mut().flat_map(|command| command.keys.iter_mut());
+        let channel_encoders = self.channel_encoders.iter_mut().flat_map(|(channels, _)| channels.iter_mut());
+        for name in channels
+            .chain(channel_encoders)
+            .chain(streams)
+            .chain(&mut self.shared_streams)
+            .chain(queues)
+            .chain(multi_streams)
+            .chain(custom_keys)
+        {
+            name.insert_str(0, &prefix);
+        }
+        for name in self.level_streams.values_mut().flatten() {
+            name.insert_str(0, &prefix);
+        }
+        #[cfg(feature = "dated_streams")]
+        if let Some(dated_stream) = &mut self.dated_stream {
+            dated_stream.base.insert_str(0, &prefix);
+        }
+        #[cfg(feature = "time_series")]
+        for target in &mut self.time_series {
+            target.prefix_key(&prefix);
+        }
+        #[cfg(feature = "compression")]
+        if let Some((channel, _)) = &mut self.compressed_channel {
+            channel.insert_str(0, &prefix);
+        }
+        if let Some((channel, _)) = &mut self.alert_channel {
+            channel.insert_str(0, &prefix);
+        }
+        self.key_prefix.insert_str(0, &prefix);
+        self
+    }
+
+    /// Additionally publishes the messages of the pub/sub encoder compressed with `compression` to the channel named
+    /// `base` with the extension of the algorithm as a suffix, e.g. `logging.gz` for the base `logging` and gzip.
+    ///
+    /// The message is encoded once and published uncompressed to the configured channels and compressed to this one.
+    /// By convention, the uncompressed channel is named with the suffix `.raw`, e.g. `logging.raw`, so subscribers can
+    /// choose between `logging.raw` and `logging.gz`. Requires pub/sub channels to be configured. See also
+    /// `CompressedPubSubEncoder` to compress the messages of all channels.
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    #[cfg(feature = "compression")]
+    #[must_use]
+    pub fn with_compressed_channel(mut self, base: &str, compression: Compression) -> Self {
+        let channel = format!("{}{base}.{}", self.key_prefix, compression.extension());
+        self.compressed_channel = Some((channel, compression));
+        self
+    }
+
+    /// Publishes a record to all configured channels with a single Lua script (`EVAL`) instead of one `PUBLISH` per
+    /// channel, so the message is sent to Redis once and fanned out server-side. This pays off for many channels or large
+    /// messages. Only applies if more than one channel is configured, and not to channels added by other options, like
+    /// `with_alert_channel`. Defaults to `false`.
+    ///
+    /// The script's reply is the total number of receivers, so `with_warn_on_no_subscribers` can't tell which of the
+    /// channels have no subscribers and ignores the scripted publish. Scripts must be allowed for the connecting user.
+    #[must_use]
+    pub const fn with_scripted_fan_out(mut self, scripted_fan_out: bool) -> Self {
+        self.scripted_fan_out = scripted_fan_out;
+        self
+    }
+
+    /// Additionally publishes the messages of the pub/sub encoder of records at or above `min_level` to `channel`, e.g. to
+    /// notify subscribers of errors in real time while streams keep the full history.
+    ///
+    /// The message is encoded once for the configured channels and the alert channel. Requires pub/sub channels to be
+    /// configured, e.g. with `RedisLoggerConfigBuilder::build_with_pubsub_and_streams`. Records routed to streams only by
+    /// `with_routing_key` are not published to the alert channel either.
+    #[must_use]
+    pub fn with_alert_channel(mut self, channel: impl Into<String>, min_level: Level) -> Self {
+        self.alert_channel = Some((format!("{}{}", self.key_prefix, channel.into()), min_level));
+        self
+    }
+
+    /// Publishes every record to the channels returned by `channels` for it instead of the configured ones, e.g. based on
+    /// a key-value or external state. The key prefix is prepended to the returned names. See `with_level_streams` to
+    /// choose streams by level.
+    ///
+    /// The closure is called in `log` for every published record, so it should be fast. If it panics, the record is not
+    /// published and the panic is reported like that of an encoder. The configured channels are only used as a check
+    /// that the logger publishes at all and are otherwise ignored.
+    #[must_use]
+    pub fn with_dynamic_channels<F>(mut self, channels: F) -> Self
+    where
+        F: Fn(&Record) -> Vec<String> + Send + Sync + 'static,
+    {
+        self.dynamic_channels = Some(Box::new(channels));
+        self
+    }
+
+    /// Additionally publishes every record to further channels, each group of channels with its own encoder, e.g. the
+    /// legacy JSON format to one channel and ECS to another while consumers migrate.
+    ///
+    /// The messages are published in the same pipeline as those to the channels of the config, and are subject to the
+    /// same routing (see `with_routing_key`). Can be called multiple times to add more channels.
+    #[must_use]
+    pub fn with_channel_encoders(mut self, channels: Vec<(Vec<String>, Box<dyn PubSubEncoder>)>) -> Self {
+        for (mut names, encoder) in channels {
+            for name in &mut names {
+                name.insert_str(0, &self.key_prefix);
+            }
+            self.channel_encoders.push((names, encoder));
+        }
+        self
+    }
+
+    /// Additionally adds every record to further streams, each group of streams with its own encoder, e.g. a compact index
+    /// stream holding only the level, target and timestamp for real-time dashboards next to the full archive stream
+    /// written with the encoder of the config.
+    ///
+    /// The entries are added in the same pipeline and with the same ID as those of the other streams, so all streams hold
+    /// the record or none of them. `with_level_streams` and the dated stream only apply to the streams of the config.
+    /// Can be called multiple times to add more streams.
+    ///
+    /// ```rust
+    /// # use redis_logger::{HybridStreamEncoder, PubSubEncoder, RedisLoggerConfigBuilder, ScalarField, StreamEncoder};
+    /// # struct MessageEncoder;
+    /// # impl PubSubEncoder for MessageEncoder {
+    /// #     fn encode(&self, record: &log::Record) -> Vec<u8> {
+    /// #         record.args().to_string().into_bytes()
+    /// #     }
+    /// # }
+    /// # fn config(archive_encoder: impl StreamEncoder + 'static) {
+    /// let connection = redis::Client::open("redis://127.0.0.1/").unwrap().get_connection().unwrap();
+    /// let index_encoder = HybridStreamEncoder::new(MessageEncoder).with_scalar_fields(vec![ScalarField::Level]);
+    /// let config = RedisLoggerConfigBuilder::build_with_streams(connection, vec!["archive".into()], archive_encoder)
+    ///     .with_multi_stream(vec![(vec!["index".into()], Box::new(index_encoder))]);
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_multi_stream(mut self, streams: Vec<(Vec<String>, Box<dyn StreamEncoder>)>) -> Self {
+        for (mut names, encoder) in streams {
+            for name in &mut names {
+                name.insert_str(0, &self.key_prefix);
+            }
+            self.multi_streams.push((names, encoder));
+        }
+        self
+    }
+
+    /// Additionally pushes every record encoded by `encoder` to the head of the list `list` with `LPUSH`, so a separate
+    /// process can consume the list as a work queue, e.g. a shipper forwarding records to long-term storage.
+    ///
+    /// Consumers should pop from the tail with `BRPOP` (or `RPOP`) to receive the records in the order they were logged.
+    /// The list is not trimmed, so it grows as long as the consumer is behind. Queues are written regardless of
+    /// `with_routing_key`. Can be called multiple times to add several queues.
+    #[must_use]
+    pub fn with_queue<E>(mut self, list: impl Into<String>, encoder: E) -> Self
+    where
+        E: PubSubEncoder + 'static,
+    {
+        let mut list = list.into();
+        list.insert_str(0, &self.key_prefix);
+        self.queues.push(Queue {
+            list,
+            encoder: Box::new(move |record| encoder.encode(record)),
+        });
+        self
+    }
+
+    /// Additionally writes every record with the command `name` of a Redis module, e.g. `LOG.WRITE` of a custom log ingest
+    /// module, once for each of the `keys`.
+    ///
+    /// The arguments are the key followed by the fields of the stream encoder, each as its name and then its value:
+    /// `<name> <key> <field 1> <value 1> <field 2> <value 2> ...`. The commands are sent in the same pipeline as the other
+    /// ones and are written regardless of `with_routing_key`. The key prefix applies to the keys. Can be called multiple
+    /// times to add several commands.
+    #[must_use]
+    pub fn with_custom_command<E>(mut self, name: impl Into<String>, keys: Vec<String>, encoder: E) -> Self
+    where
+        E: StreamEncoder + 'static,
+    {
+        let keys = keys.into_iter().map(|key| format!("{}{key}", self.key_prefix)).collect();
+        self.custom_commands.push(CustomCommand {
+            name: name.into(),
+            keys,
+            encoder: Box::new(encoder),
+        });
+        self
+    }
+
+    /// Additionally adds a numeric key-value of records as a sample to a RedisTimeSeries key, see `TimeSeriesTarget`.
+    /// Can be called multiple times to add several targets.
+    #[cfg_attr(docsrs, doc(cfg(feature = "time_series")))]
+    #[cfg(feature = "time_series")]
+    #[must_use]
+    pub fn with_time_series(mut self, mut target: TimeSeriesTarget) -> Self {
+        target.prefix_key(&self.key_prefix);
+        self.time_series.push(target);
+        self
+    }
+
+    /// Routes records to streams by their level, e.g. errors to a long-retention stream and debug records to a short-lived one.
+    ///
+    /// Records of a level contained in `level_streams` are added to the given streams only, records of all other levels
+    /// fall back to the streams passed to the builder. All streams use the same stream encoder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no streams are configured, as the stream encoder is used for the level streams.
+    #[must_use]
+    pub fn with_level_streams(mut self, level_streams: HashMap<Level, Vec<String>>) -> Self {
+        assert!(
+            self.streams.is_some(),
+            "Level streams require a stream encoder. Configure at least one stream."
+        );
+        self.level_streams = level_streams
+            .into_iter()
+            .map(|(level, streams)| {
+                (
+                    level,
+                    streams
+                        .into_iter()
+                        .map(|stream| format!("{}{stream}", self.key_prefix))
+                        .collect(),
+                )
+            })
+            .collect();
+        self
+    }
+
+    /// Additionally adds every record to a stream named after the current date, e.g. `logs:2024-06-01` for the base
+    /// `"logs:"` and the format `"%Y-%m-%d"`. The name is computed for every record from the current UTC date using
+    /// `chrono`'s `strftime` syntax, so each day gets its own stream and old ones can be removed by key.
+    ///
+    /// This method is only available when the `dated_streams` feature is enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no streams are configured, as the stream encoder is used for the dated stream.
+    #[cfg_attr(docsrs, doc(cfg(feature = "dated_streams")))]
+    #[cfg(feature = "dated_streams")]
+    #[must_use]
+    pub fn with_dated_stream(mut self, base: impl Into<String>, format: &str) -> Self {
+        assert!(
+            self.streams.is_some(),
+            "Dated streams require a stream encoder. Configure at least one stream."
+        );
+        self.dated_stream = Some(DatedStream {
+            base: format!("{}{}", self.key_prefix, base.into()),
+            format: format.to_owned(),
+            now: Box::new(chrono::Utc::now),
+        });
+        self
+    }
+
+    /// Limits the number of commands sent in one pipeline. If a record produces more commands (one per channel and stream,
+    /// plus trimming or `WAIT`), they are split into several pipelines that are sent one after the other.
+    ///
+    /// This bounds the size of a single write at the cost of additional round trips. Defaults to no limit.
+    /// A value of 0 is treated as 1.
+    #[must_use]
+    pub const fn with_max_pipeline_commands(mut self, max_pipeline_commands: usize) -> Self {
+        self.max_pipeline_commands = if max_pipeline_commands == 0 {
+            1
+        } else {
+            max_pipeline_commands
+        };
+        self
+    }
+
+    /// Reads the log level from the Redis key `key` every `poll_interval`, so the level of all instances can be controlled
+    /// centrally by setting one key, e.g. `SET config:log_level debug`.
+    ///
+    /// A background thread reads the key using the logger's connection until the logger is dropped. Values are parsed
+    /// like `LevelFilter::from_str` (e.g. `info`, `DEBUG`, `off`). Invalid values are ignored and reported to the error handler,
+    /// a missing key keeps the current level.
+    ///
+    /// If the remote level is more verbose than `log::max_level()`, the global max level is raised accordingly, as the
+    /// `log` macros would filter the records otherwise. It is never lowered, as other loggers may depend on it.
+    #[must_use]
+    pub fn with_remote_level_key(mut self, key: impl Into<String>, poll_interval: Duration) -> Self {
+        self.remote_level = Some((key.into(), poll_interval));
+        self
+    }
+
+    /// Writes records to stderr instead while Redis is not reachable yet, e.g. because the server is still starting.
+    /// Once a write to Redis succeeds, the logger switches over and never writes to stderr again.
+    ///
+    /// The records are written as encoded for Redis by the pub/sub encoder, or as `field=value` pairs of the stream encoder
+    /// if the pub/sub encoder didn't encode them, one record per line. Errors are still reported to the error handler.
+    #[must_use]
+    pub fn with_stderr_until_connected(mut self, enabled: bool) -> Self {
+        self.stderr_fallback = enabled.then(|| StderrFallback {
+            connected: AtomicBool::new(false),
+            writer: Mutex::new(Box::new(io::stderr())),
+        });
+        self
+    }
+
+    /// Appends commands that failed with a transient error (see `is_retryable`) after all retries to the file at `path`
+    /// instead of keeping them in memory, so they survive a crash or restart during a Redis outage.
+    ///
+    /// The spool is replayed in order before the next record is written, including a spool left by a previous run,
+    /// and truncated once everything has been sent. Every append is synced to disk. An incomplete last entry left by a
+    /// crash during an append is skipped on replay.
+    ///
+    /// The spool is not rotated: once it holds `max_size` bytes, further records are dropped and reported to the error
+    /// handler until it has been replayed. Takes precedence over `with_retry_buffer`.
+    #[must_use]
+    pub fn with_disk_spool(mut self, path: impl Into<PathBuf>, max_size: u64) -> Self {
+        self.disk_spool = Some(DiskSpool::new(path.into(), max_size));
+        self
+    }
+
+    /// Sets how to create a new connection for `RedisLogger::reconnect`, e.g. `move || client.get_connection()` with
+    /// the `redis::Client` the initial connection was created from.
+    ///
+    /// The function is also used to replace the connection after a write timed out, as the late reply to the timed out
+    /// command could otherwise be taken for the reply to the next one. Retries then use the new connection.
+    #[must_use]
+    pub fn with_connect<F>(mut self, connect: F) -> Self
+    where
+        F: Fn() -> redis::RedisResult<CONN> + Send + Sync + 'static,
+    {
+        self.connect = Some(Box::new(connect));
+        self
+    }
+
+    /// Sets what the connection connects to, e.g. `client.get_connection_info()` of the `redis::Client` the connection was
+    /// created from, to be reported by `RedisLogger::connection_info` for diagnostics. The password is not kept.
+    #[must_use]
+    pub fn with_connection_info(mut self, info: &redis::ConnectionInfo) -> Self {
+        let (host, port, tls) = match &info.addr {
+            redis::ConnectionAddr::Tcp(host, port) => (host.clone(), Some(*port), false),
+            redis::ConnectionAddr::TcpTls { host, port, .. } => (host.clone(), Some(*port), true),
+            redis::ConnectionAddr::Unix(path) => (path.display().to_string(), None, false),
+        };
+        self.connection_info = Some(LoggerConnectionInfo {
+            host: Some(host),
+            port,
+            db: Some(info.redis.db),
+            tls,
+            username: info.redis.username.clone(),
+            has_password: info.redis.password.is_some(),
+            channels: 0,
+            streams: 0,
+        });
+        self
+    }
+
+    /// Names the logger's connection with `CLIENT SETNAME` when the logger is created and after every
+    /// `RedisLogger::reconnect`, so it can be told apart in the output of `CLIENT LIST`. The name must not contain spaces.
+    /// Failing to set the name is reported to the error handler, but doesn't prevent logging.
+    #[must_use]
+    pub fn with_client_name(mut self, name: impl Into<String>) -> Self {
+        self.client_name = Some(name.into());
+        self
+    }
+
+    /// Thins out noisy targets by only logging one of every `rate` records of a target and its submodules, e.g.
+    /// `{"myapp::poller": 100}` logs every 100th record of `myapp::poller` while all other targets are logged completely.
+    ///
+    /// If several targets match, the longest one applies, so `{"myapp": 10, "myapp::payments": 1}` keeps every record of
+    /// `myapp::payments` but only every 10th of the rest of `myapp`. The first record of a target is always logged.
+    /// A rate of 0 is treated as 1. The decision is made once per record before it is encoded, so a record is either
+    /// written to all channels, streams and other targets or to none of them.
+    #[must_use]
+    pub fn with_target_sampling(mut self, target_sampling: HashMap<String, u32>) -> Self {
+        let mut target_sampling: Vec<TargetSampling> = target_sampling
+            .into_iter()
+            .map(|(prefix, rate)| TargetSampling {
+                prefix,
+                rate: u64::from(rate.max(1)),
+                records: AtomicU64::new(0),
+            })
+            .collect();
+        target_sampling.sort_by_key(|sampling| std::cmp::Reverse(sampling.prefix.len()));
+        self.target_sampling = target_sampling;
+        self
+    }
+
+    /// Suppresses records that any logger sharing the Redis instance has written within `ttl`, e.g. the same error
+    /// reported by many instances of a service at once. Records are the same if their hashes are, which by default are
+    /// made of their level, target and message (see `with_record_hasher`).
+    ///
+    /// Before a record is written, `SET <key> 1 NX EX <ttl>` is sent in its own round trip, with a key made of the
+    /// key prefix, `redis_logger:dedup:` and a hash of the record. The record is only written if the key didn't exist.
+    /// If the `SET` fails, the record is written anyway, so no record is lost to deduplication. The TTL is rounded down
+    /// to whole seconds, with a minimum of one second.
+    #[must_use]
+    pub const fn with_redis_dedup(mut self, ttl: Duration) -> Self {
+        self.dedup_ttl = Some(ttl);
+        self
+    }
+
+    /// Sets how records are hashed for `with_sharded_channels` and `with_redis_dedup`, e.g. to only hash the message or
+    /// to ignore variable parts of it. Defaults to `DefaultRecordHasher`.
+    #[must_use]
+    pub fn with_record_hasher<H: RecordHasher + 'static>(mut self, hasher: H) -> Self {
+        self.record_hasher = Box::new(hasher);
+        self
+    }
+
+    /// Publishes every record to only one of the configured channels instead of all of them, chosen by the hash of the
+    /// record (see `with_record_hasher`), so the records are spread over the channels, e.g. for subscribers processing
+    /// one channel each. Records with the same hash always go to the same channel. Defaults to `false`.
+    #[must_use]
+    pub const fn with_sharded_channels(mut self, sharded_channels: bool) -> Self {
+        self.sharded_channels = sharded_channels;
+        self
+    }
+
+    /// Writes a marker record to the configured channels and streams at most once per `interval` if anything was dropped
+    /// since the last marker, so consumers see the gap. The marker is checked for before the next record is written.
+    ///
+    /// One marker is written per reason, with the level `WARN`, the target `redis_logger`, a message like
+    /// `12 dropped (sampling)` and the key-values `dropped` and `reason`. The reasons are `sampling` for records thinned out
+    /// by `with_target_sampling`, `retry_buffer_full` for commands evicted from the retry buffer, `spool_failed` for
+    /// commands that could not be written to the disk spool, `encoder_panic` for records an encoder panicked on,
+    /// `duplicate` for records suppressed by `with_redis_dedup`, and `shedding` for records shed by
+    /// `with_adaptive_shedding`. Every marker resets the count it reports.
+    #[must_use]
+    pub fn with_drop_markers(mut self, interval: Duration) -> Self {
+        self.drop_markers = Some(DropMarkers {
+            interval,
+            last: Mutex::new(Instant::now()),
+            dropped: Default::default(),
+        });
+        self
+    }
+
+    /// Reports `RedisLoggerError::NoSubscribers` to the error handler if a `PUBLISH` reached no subscribers, as the message
+    /// is lost then. Consistently missing subscribers often point to a misconfigured channel name.
+    ///
+    /// The replies of the pipeline are inspected after every write. To not flood the error handler, channels without
+    /// subscribers are reported at most once a minute. Commands replayed from the retry buffer or the disk spool are not
+    /// inspected.
+    #[must_use]
+    pub fn with_warn_on_no_subscribers(mut self, warn: bool) -> Self {
+        self.no_subscribers_warning = warn.then(|| Mutex::new(None));
+        self
+    }
+
+    /// Lets the call site choose the targets of a record with the boolean key-value `key`. With the key `persist`,
+    /// `log::info!(persist = true; "Order placed")` is only added to the streams and `log::info!(persist = false; "Cart
+    /// updated")` is only published to the channels. Records without the key, or with a value that is not a boolean,
+    /// are written to all configured channels and streams.
+    ///
+    /// Streams shared with the channels (see `RedisLoggerConfigBuilder::build_with_pubsub_and_stream_shared`) count as
+    /// streams. Time series (see `with_time_series`) are written regardless of the key.
+    #[must_use]
+    pub fn with_routing_key(mut self, key: impl Into<String>) -> Self {
+        self.routing_key = Some(key.into());
+        self
+    }
+
+    /// Drops records of the given targets and their submodules, e.g. `"hyper"` drops the targets `hyper` and
+    /// `hyper::client` but not `hyper_util`.
+    ///
+    /// Defaults to `["redis"]`, so records the `redis` crate logs itself never reach Redis. Otherwise, logging about a
+    /// failing connection could write more records to the same failing connection during an incident.
+    /// Pass an empty vector to log all targets.
+    #[must_use]
+    pub fn with_ignored_targets(mut self, targets: Vec<String>) -> Self {
+        self.ignored_targets = targets;
+        self
+    }
+
+    /// Keeps up to `capacity` commands that failed with a transient error (see `is_retryable`) after all retries in a buffer.
+    /// The buffered commands are sent again in their original order before the next record is written.
+    ///
+    /// If the buffer is full, the oldest commands are dropped. Use `RedisLogger::take_buffered` to take the buffered
+    /// records out, e.g. to persist them elsewhere on shutdown.
+    #[must_use]
+    pub fn with_retry_buffer(mut self, capacity: usize) -> Self {
+        let shedding = self.retry_buffer.as_ref().and_then(|buffer| buffer.shedding);
+        self.retry_buffer = (capacity > 0).then(|| RetryBuffer {
+            capacity,
+            records: Mutex::new(VecDeque::with_capacity(capacity)),
+            shedding,
+        });
+        self
+    }
+
+    /// Drops records of the level `shed_below` and less severe ones while the retry buffer (see `with_retry_buffer`) is
+    /// more than `threshold_pct` percent full, so an outage fills the buffer with important records instead of debug
+    /// output. Warnings and errors are always kept, even if `shed_below` is `Warn` or `Error`.
+    ///
+    /// The decision is made before a record is encoded. Shed records are counted as drops (see `with_drop_markers`).
+    /// Has no effect without a retry buffer, so call this after `with_retry_buffer`.
+    #[must_use]
+    pub fn with_adaptive_shedding(mut self, threshold_pct: u8, shed_below: Level) -> Self {
+        if let Some(buffer) = &mut self.retry_buffer {
+            buffer.shedding = Some((threshold_pct.min(100), shed_below));
+        }
+        self
+    }
+
+    /// Sets whether each channel and stream is written with its own query instead of all of them in one pipeline.
+    ///
+    /// By default, all commands for a record are sent in a single pipeline, so one failing command (e.g. a stream name that
+    /// holds a key of the wrong type) fails the whole batch. With independent targets, every channel and stream is written
+    /// separately and failures are reported per target, trading the single round trip for isolation between targets.
+    ///
+    /// Note that a pipeline is not a transaction: Redis runs every command of it even if one of them fails, so the other
+    /// targets of a failed pipeline usually did receive the record. The reply only carries the first error though, without
+    /// telling which command failed, so the logger can neither report the failing targets nor resend just those.
+    /// The failure is reported once without a target and the commands are not retried, as retrying would duplicate the
+    /// delivered records. Enable independent targets or `with_per_command_results` if you need to know exactly which
+    /// targets failed.
+    #[must_use]
+    pub const fn with_independent_targets(mut self, independent_targets: bool) -> Self {
+        self.independent_targets = independent_targets;
+        self
+    }
+
+    /// Sets whether the replies to a pipeline are read one by one, so a command failing with an error reply, e.g.
+    /// `WRONGTYPE` for a stream name that holds a key of another type, only fails its own target.
+    ///
+    /// The pipeline is still sent in one round trip. Every failing command is reported with its target, and only the
+    /// commands failing with a transient error are kept in the retry buffer, as all other targets are known to have
+    /// received the record. If the connection fails while the replies are read, the whole pipeline fails as without
+    /// this option.
+    ///
+    /// Supported connections are `redis::Connection`, `StreamConnection` and `CapturingConnection`. The replies are read
+    /// by calling `req_packed_commands(&[], 0, 1)` once per command, so a custom `ConnectionLike` only works if it
+    /// writes an empty buffer as a no-op and reads exactly `offset + count` replies from the same server on every call.
+    /// This is not the case for `redis::cluster::ClusterConnection`, which routes every call on its own, nor for mocks
+    /// or proxies that answer each call with its own replies. Don't enable this option with such connections.
+    #[must_use]
+    pub const fn with_per_command_results(mut self, per_command_results: bool) -> Self {
+        self.per_command_results = per_command_results;
+        self
+    }
+
+    /// Appends `WAIT replicas timeout` after the stream entries are added, so logging a record only returns once the
+    /// entries have been acknowledged by `replicas` replicas or the `timeout` has passed.
+    ///
+    /// Use this for durability-critical logs only: every record then costs at least one round trip to the replicas,
+    /// and up to `timeout` if not enough replicas are reachable. The number of acknowledging replicas returned by `WAIT`
+    /// is not checked, so a record may still not be replicated after the timeout. Has no effect without streams.
+    #[must_use]
+    pub const fn with_wait_replicas(mut self, replicas: usize, timeout: Duration) -> Self {
+        self.wait_replicas = Some((replicas, timeout));
+        self
+    }
+
+    /// Caps every stream at about `maxlen` entries by adding `MAXLEN ~ maxlen` to each `XADD`.
+    ///
+    /// Trimming is approximate (`~`), so Redis may keep a few more entries if that makes trimming more efficient.
+    #[must_use]
+    pub const fn with_stream_maxlen(mut self, maxlen: usize) -> Self {
+        self.stream_trim.maxlen = Some(maxlen);
+        self
+    }
+
+    /// Caps every stream at about `maxlen` entries with an `XTRIM <stream> MAXLEN ~ maxlen` every `interval` from a
+    /// background thread instead of trimming with every `XADD`, which takes the trimming off the write path of high volume
+    /// streams. Streams may grow beyond `maxlen` between two trims, so choose the interval according to the write rate.
+    ///
+    /// Trims the configured streams, shared streams, streams of `with_multi_stream` and `with_level_streams`, but not
+    /// dated streams. Errors are reported to the error handler. The thread stops within one interval after the logger is
+    /// dropped or its configuration replaced by `RedisLogger::reload`.
+    #[must_use]
+    pub const fn with_background_trim(mut self, maxlen: usize, interval: Duration) -> Self {
+        self.background_trim = Some((maxlen, interval));
+        self
+    }
+
+    /// Adds an entry with the single field `heartbeat`, holding the current time in milliseconds since the Unix epoch, to
+    /// every stream when no record was written for `interval`, and again after every further `interval` without records.
+    /// Consumers can tell a quiet logger from a dead one this way, while busy periods are not cluttered with heartbeats.
+    ///
+    /// The heartbeats are written from a background thread to the streams that `with_background_trim` trims, trimmed with
+    /// `with_stream_maxlen` and restricted by `with_no_mkstream`. Errors are reported to the error handler. The thread stops
+    /// within one interval after the logger is dropped or its configuration replaced by `RedisLogger::reload`.
+    #[must_use]
+    pub fn with_idle_heartbeat(mut self, interval: Duration) -> Self {
+        self.idle_heartbeat = Some(IdleHeartbeat {
+            interval,
+            start: Instant::now(),
+            last_entry: Arc::new(AtomicU64::new(0)),
+        });
+        self
+    }
+
+    /// Removes stream entries older than `min_age` by adding `MINID ~ <now - min_age>` to each `XADD`.
+    ///
+    /// If `with_stream_maxlen` is set as well, `XADD` can only apply one of both strategies. In that case, `XADD` uses `MAXLEN`
+    /// and an additional `XTRIM` with `MINID` is sent with every `trim_interval`-th record (see `with_trim_interval`),
+    /// so a stream holds at most `maxlen` entries and none older than `min_age`. This is an approximation: between two
+    /// `XTRIM`s, entries older than `min_age` may remain in the stream.
+    #[must_use]
+    pub const fn with_stream_min_age(mut self, min_age: Duration) -> Self {
+        self.stream_trim.min_age = Some(min_age);
+        self
+    }
+
+    /// Limits how many entries a single `XADD` or `XTRIM` may evict while trimming by adding `LIMIT limit` after the
+    /// `MAXLEN` or `MINID` clause, so adding to a huge stream doesn't block Redis for long. The remaining entries are
+    /// evicted by later commands. Requires Redis 6.2 or later and has no effect without `with_stream_maxlen` or
+    /// `with_stream_min_age`.
+    #[must_use]
+    pub const fn with_stream_trim_limit(mut self, limit: usize) -> Self {
+        self.stream_trim.limit = Some(limit);
+        self
+    }
+
+    /// Sets after how many records the `XTRIM` with `MINID` is sent if both `with_stream_maxlen` and `with_stream_min_age`
+    /// are set. Defaults to 100. A value of 0 is treated as 1.
+    #[must_use]
+    pub const fn with_trim_interval(mut self, trim_interval: u64) -> Self {
+        self.stream_trim.interval = if trim_interval == 0 { 1 } else { trim_interval };
+        self
+    }
+
+    /// Adds stream entries with ids of the form `<epoch_ms>-<seq>` generated by the logger instead of letting Redis
+    /// assign them with `*`. The ids strictly increase in the order the records are logged, even if the system clock
+    /// goes backwards, which makes the order of the entries of strictly ordered streams like audit logs explicit.
+    /// All streams a record is added to get the same id.
+    ///
+    /// Redis rejects ids that are not greater than the id of the last entry, e.g. if another producer with a clock
+    /// running ahead writes to the same stream. The rejected entries are then added again with `*`. As Redis doesn't
+    /// tell which `XADD` of a pipeline was rejected, entries added to other streams by the same pipeline are added twice.
+    /// Use `with_independent_targets` or `with_per_command_results` to avoid that.
+    #[must_use]
+    pub fn with_custom_stream_ids(mut self, custom_stream_ids: bool) -> Self {
+        self.stream_ids = custom_stream_ids.then(StreamIds::default);
+        self
+    }
+
+    /// Adds `NOMKSTREAM` to every `XADD`, so the logger never creates a stream, e.g. without the retention and consumer
+    /// groups an operator provisions streams with. Redis ignores an `XADD` to a missing stream, so records for it are
+    /// lost without an error. Create the streams first, e.g. with `provision_streams`. Requires Redis 6.2 or later.
+    /// Defaults to `false`.
+    #[must_use]
+    pub const fn with_no_mkstream(mut self, no_mkstream: bool) -> Self {
+        self.stream_trim.no_mkstream = no_mkstream;
+        self
     }
 
-    /// Initializes the Redis logger with the specified log level and configuration.
-    ///
-    /// # Arguments
+    /// Creates the streams the logger writes to that don't exist yet and trims all of them to the retention set with
+    /// `with_stream_maxlen` or `with_stream_min_age`. With a `group`, the consumer group is created on every stream
+    /// reading new entries only, unless it already exists. Redis has no retention setting of its own for streams, so the
+    /// logger keeps applying the retention with every `XADD`.
     ///
-    /// * `level` - The log level to set for the logger.
-    /// * `config` - The configuration for the Redis logger.
+    /// Call this at startup before creating the logger, and combine it with `with_no_mkstream`, so the streams exist
+    /// with the expected setup before any record is written and the logger never creates another one:
     ///
-    /// # Returns
+    /// ```rust,no_run
+    /// # use log::LevelFilter;
+    /// # use redis_logger::{RedisLogger, RedisLoggerConfigBuilder, StreamEncoder};
+    /// # struct MessageEncoder;
+    /// # impl StreamEncoder for MessageEncoder {
+    /// #     fn encode(&self, record: &log::Record) -> Vec<(String, Vec<u8>)> {
+    /// #         vec![("message".to_owned(), record.args().to_string().into_bytes())]
+    /// #     }
+    /// # }
+    /// let connection = redis::Client::open("redis://127.0.0.1/").unwrap().get_connection().unwrap();
+    /// let config = RedisLoggerConfigBuilder::build_with_streams(connection, vec!["logging".into()], MessageEncoder)
+    ///     .with_stream_maxlen(100_000)
+    ///     .with_no_mkstream(true);
+    /// config.provision_streams(Some("log-consumers")).unwrap();
+    /// RedisLogger::init(LevelFilter::Info, config).unwrap();
+    /// ```
     ///
-    /// Result indicating success or an error of type `RedisLoggerConfigError`.
-    /// If successful, the logger is set as the global logger.
+    /// Streams whose names are only known per record, like a dated stream, are not created.
     ///
     /// # Errors
     ///
-    /// see above
-    pub fn init(level: LevelFilter, config: RedisLoggerConfig<CONN, PUBSUB, STREAM>) -> Result<(), SetLoggerError> {
-        let redis_logger = Self::new(level, config);
-        log::set_max_level(level);
-        log::set_boxed_logger(redis_logger)?;
+    /// Returns the first error creating or trimming a stream, e.g. if a key of another type has the name of a stream.
+    pub fn provision_streams(&self, group: Option<&str>) -> redis::RedisResult<()> {
+        // without a group, a temporary one is created, as `XGROUP CREATE ... MKSTREAM` creates a stream without adding
+        // an entry that consumers would read
+        const PROVISION_GROUP: &str = "redis_logger:provision";
+        // this unwrap only panics if the connection is poisoned, so we can't do much anyway and will panic, too!
+        let mut connection = self.connection.lock().unwrap();
+        for stream in self.static_streams() {
+            let created = redis::cmd("XGROUP")
+                .arg("CREATE")
+                .arg(&stream)
+                .arg(group.unwrap_or(PROVISION_GROUP))
+                .arg("$")
+                .arg("MKSTREAM")
+                .query::<()>(&mut *connection);
+            match created {
+                Err(error) if error.code() != Some("BUSYGROUP") => return Err(error),
+                _ => {}
+            }
+            if group.is_none() {
+                redis::cmd("XGROUP")
+                    .arg("DESTROY")
+                    .arg(&stream)
+                    .arg(PROVISION_GROUP)
+                    .query::<()>(&mut *connection)?;
+            }
+            if let Some(xtrim) = self.stream_trim.retention(&stream) {
+                xtrim.query::<()>(&mut *connection)?;
+            }
+        }
         Ok(())
     }
-}
 
-/// Implements the `Log` trait for the `RedisLogger` struct.
-///
-/// This implementation provides the necessary methods to enable logging to Redis.
-/// The `enabled` method checks if the log level of the provided `Metadata` is less than or equal to the configured log level.
-/// The `log` method publishes log messages to Redis channels and streams based on the configuration in one atomic operation using a pipeline.
-/// The `flush` method is a no-op in this implementation.
-impl<CONN, PUBSUB, STREAM> Log for RedisLogger<CONN, PUBSUB, STREAM>
-where
-    CONN: ConnectionLike + Send + Sync,
-    PUBSUB: PubSubEncoder,
-    STREAM: StreamEncoder,
-{
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= self.level
+    /// Returns the names of all streams that are known up front, i.e. not chosen per record, without duplicates.
+    fn static_streams(&self) -> Vec<String> {
+        let mut streams: Vec<String> = self.streams.iter().flat_map(|(streams, _)| streams.clone()).collect();
+        streams.extend(self.shared_streams.iter().cloned());
+        streams.extend(self.multi_streams.iter().flat_map(|(streams, _)| streams.clone()));
+        streams.extend(self.level_streams.values().flatten().cloned());
+        streams.sort();
+        streams.dedup();
+        streams
     }
 
-    fn log(&self, record: &Record) {
-        if self.enabled(record.metadata()) {
-            let config = &self.config;
-            let mut pipe = redis::pipe();
-            if let Some((channels, encoder)) = &config.channels {
-                let message = encoder.encode(record);
+    fn count_dropped(&self, reason: DropReason, count: u64) {
+        if let Some(markers) = &self.drop_markers {
+            markers.dropped[reason as usize].fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    /// Writes a marker for every reason with drops if the marker interval has passed.
+    fn write_drop_markers(&self) {
+        let Some(markers) = &self.drop_markers else {
+            return;
+        };
+        {
+            let mut last = markers.last.lock().unwrap();
+            if last.elapsed() < markers.interval {
+                return;
+            }
+            *last = Instant::now();
+        }
+        for reason in DropReason::ALL {
+            let dropped = markers.dropped[reason as usize].swap(0, Ordering::Relaxed);
+            if dropped == 0 {
+                continue;
+            }
+            let key_values = [
+                ("dropped", log::kv::Value::from(dropped)),
+                ("reason", log::kv::Value::from(reason.as_str())),
+            ];
+            let commands = self.commands(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .target("redis_logger")
+                    .args(format_args!("{dropped} dropped ({})", reason.as_str()))
+                    .key_values(&key_values)
+                    .build(),
+                &mut None,
+            );
+            self.send(&commands);
+        }
+    }
+
+    fn is_disabled(&self) -> bool {
+        self.retry_budget
+            .as_ref()
+            .is_some_and(|budget| budget.disabled.load(Ordering::Relaxed))
+    }
+
+    /// Counts a failed write. Returns `true` if this failure exhausted the retry budget and disabled the logger.
+    fn count_failure(&self) -> bool {
+        let Some(budget) = &self.retry_budget else {
+            return false;
+        };
+        budget.failures.fetch_add(1, Ordering::Relaxed) + 1 >= budget.max && !budget.disabled.swap(true, Ordering::Relaxed)
+    }
+
+    fn report_disabled(&self) {
+        if let Some(budget) = &self.retry_budget {
+            self.report(RedisLoggerError::Disabled {
+                failures: budget.failures.load(Ordering::Relaxed),
+            });
+        }
+    }
+
+    fn is_ignored_target(&self, target: &str) -> bool {
+        self.ignored_targets.iter().any(|ignored| is_in_target(target, ignored))
+    }
+
+    /// Returns `false` if the record is thinned out by the sampling rate of the longest matching target prefix.
+    fn is_sampled(&self, target: &str) -> bool {
+        self.target_sampling
+            .iter()
+            .find(|sampling| is_in_target(target, &sampling.prefix))
+            .map_or(true, |sampling| {
+                sampling.records.fetch_add(1, Ordering::Relaxed) % sampling.rate == 0
+            })
+    }
+
+    /// Creates a new connection with the function set with `with_connect` and sets the client name on it.
+    fn new_connection(&self) -> redis::RedisResult<CONN> {
+        let Some(connect) = &self.connect else {
+            return Err(RedisError::from((
+                ErrorKind::ClientError,
+                "No function to connect is configured, see RedisLoggerConfig::with_connect",
+            )));
+        };
+        let mut connection = connect().inspect_err(|error| self.set_last_error(error))?;
+        if let Err(error) = self.set_client_name(&mut connection) {
+            self.report(RedisLoggerError::Redis {
+                retryable: is_retryable(&error),
+                error,
+                target: None,
+            });
+        }
+        Ok(connection)
+    }
+
+    /// Sets the configured client name on the connection. The error is returned rather than reported, so callers can
+    /// report it once the connection is unlocked.
+    fn set_client_name(&self, connection: &mut CONN) -> redis::RedisResult<()> {
+        match &self.client_name {
+            Some(name) => redis::cmd("CLIENT").arg("SETNAME").arg(name).query(connection),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns `true` if records of the level are shed because the retry buffer is filled above the threshold.
+    fn is_shed(&self, level: Level) -> bool {
+        let Some((buffer, (threshold_pct, shed_below))) =
+            self.retry_buffer.as_ref().and_then(|buffer| Some((buffer, buffer.shedding?)))
+        else {
+            return false;
+        };
+        level > Level::Warn
+            && level >= shed_below
+            && buffer.records.lock().unwrap().len() * 100 > buffer.capacity * usize::from(threshold_pct)
+    }
+
+    /// Returns `true` if deduplication is enabled and the record was already written within the TTL. Claims the record
+    /// for this logger otherwise.
+    fn is_duplicate(&self, record: &Record) -> bool {
+        let Some(ttl) = self.dedup_ttl else {
+            return false;
+        };
+        let key = format!(
+            "{}redis_logger:dedup:{:016x}",
+            self.key_prefix,
+            self.record_hasher.hash(record)
+        );
+        let mut set = redis::cmd("SET");
+        set.arg(&key).arg(1).arg("NX").arg("EX").arg(ttl.as_secs().max(1));
+        if self.command_observer.is_some() || self.dry_run {
+            self.observe(&[(Cow::Owned(key), set.clone())]);
+            // claiming the record in a dry run would suppress the real writes of other loggers
+            if self.dry_run {
+                return false;
+            }
+        }
+        // this unwrap only panics if the connection is poisoned, so we can't do much anyway and will panic, too!
+        let reply = self.connection.lock().unwrap().req_packed_command(&set.get_packed_command());
+        // on errors, the record is written anyway and the write reports the error
+        matches!(reply, Ok(redis::Value::Nil))
+    }
+
+    /// Returns `true` if records of the given level are written to at least one channel or stream.
+    fn has_targets(&self, level: Level) -> bool {
+        let has_channels = self.channel_encoders.iter().any(|(channels, _)| !channels.is_empty());
+        if self.channels.is_some() || has_channels || self.multi_streams.iter().any(|(streams, _)| !streams.is_empty()) {
+            return true;
+        }
+        let Some((streams, _)) = &self.streams else {
+            return false;
+        };
+        #[cfg(feature = "dated_streams")]
+        if self.dated_stream.is_some() {
+            return true;
+        }
+        !self.level_streams.get(&level).unwrap_or(streams).is_empty()
+    }
+
+    /// Returns whether the record is published to the channels and whether it is added to the streams, as chosen by the
+    /// value of the routing key.
+    fn routes(&self, record: &Record) -> (bool, bool) {
+        let persist = self
+            .routing_key
+            .as_ref()
+            .and_then(|key| log::kv::Source::get(record.key_values(), log::kv::Key::from_str(key)))
+            .and_then(|value| value.to_bool());
+        persist.map_or((true, true), |persist| (!persist, persist))
+    }
+
+    /// Runs an encoder, catching a panic so that a buggy encoder doesn't prevent the targets of the other encoder from
+    /// receiving the record. A panic is counted as a drop and reported, and `None` is returned to skip the targets.
+    fn encode_isolated<T>(&self, encoder: &'static str, encode: impl FnOnce() -> T) -> Option<T> {
+        match panic::catch_unwind(AssertUnwindSafe(encode)) {
+            Ok(output) => Some(output),
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| (*message).to_owned())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                self.count_dropped(DropReason::EncoderPanic, 1);
+                self.report(RedisLoggerError::EncoderPanic { encoder, message });
+                None
+            }
+        }
+    }
+
+    /// Encodes the record and returns the commands to send, each with the channel or stream it writes to.
+    /// While the stderr fallback is active, the line to write to stderr if sending fails is stored in `fallback`, made of
+    /// the pub/sub message or else the stream fields, so the record is not encoded again.
+    fn commands(&self, record: &Record, fallback: &mut Option<Vec<u8>>) -> Vec<Command<'_>> {
+        let mut commands = Vec::new();
+        let (publish, persist) = self.routes(record);
+        let wants_fallback = self
+            .stderr_fallback
+            .as_ref()
+            .is_some_and(|fallback| !fallback.connected.load(Ordering::Relaxed));
+        let mut pubsub_message = None;
+        if let Some((channels, encoder)) = self.channels.as_ref().filter(|_| publish || !self.shared_streams.is_empty()) {
+            if let Some(message) = self.encode_isolated("pub/sub", || encoder.encode(record)) {
+                if let Some(observer) = &self.size_observer {
+                    observer(message.len());
+                }
+                if publish {
+                    match &self.dynamic_channels {
+                        Some(dynamic_channels) => {
+                            for channel in self
+                                .encode_isolated("dynamic channels", || dynamic_channels(record))
+                                .unwrap_or_default()
+                            {
+                                let channel = format!("{}{channel}", self.key_prefix);
+                                let publish = redis::Cmd::publish(&channel, &message);
+                                commands.push((Cow::Owned(channel), publish));
+                            }
+                        }
+                        None if self.sharded_channels => {
+                            let shard = self.record_hasher.hash(record) % channels.len() as u64;
+                            let channel = &channels[shard as usize];
+                            commands.push((Cow::Borrowed(channel.as_str()), redis::Cmd::publish(channel, &message)));
+                        }
+                        None if self.scripted_fan_out && channels.len() > 1 => {
+                            let mut eval = redis::cmd("EVAL");
+                            eval.arg(FAN_OUT_SCRIPT).arg(0).arg(&message).arg(channels);
+                            commands.push((Cow::Owned(channels.join(",")), eval));
+                        }
+                        None => {
+                            for channel in channels {
+                                commands.push((Cow::Borrowed(channel.as_str()), redis::Cmd::publish(channel, &message)));
+                            }
+                        }
+                    }
+                    if let Some((channel, _)) = self.alert_channel.as_ref().filter(|(_, level)| record.level() <= *level) {
+                        commands.push((Cow::Borrowed(channel.as_str()), redis::Cmd::publish(channel, &message)));
+                    }
+                    #[cfg(feature = "compression")]
+                    if let Some((channel, compression)) = &self.compressed_channel {
+                        let compressed = compression.compress(&message);
+                        commands.push((Cow::Borrowed(channel.as_str()), redis::Cmd::publish(channel, compressed)));
+                    }
+                }
+                if wants_fallback {
+                    *fallback = Some(message.clone());
+                }
+                pubsub_message = Some(message);
+            }
+        }
+        for (channels, encoder) in self.channel_encoders.iter().filter(|_| publish) {
+            if let Some(message) = self.encode_isolated("pub/sub", || encoder.encode(record)) {
+                if let Some(observer) = &self.size_observer {
+                    observer(message.len());
+                }
                 for channel in channels {
-                    pipe.publish(channel, &message);
+                    commands.push((Cow::Borrowed(channel.as_str()), redis::Cmd::publish(channel, &message)));
                 }
             }
-            if let Some((streams, encoder)) = &config.streams {
-                let message = encoder.encode(record);
-                let message = message.as_slice();
+        }
+        let first_stream = commands.len();
+        let id = self.stream_ids.as_ref().map_or_else(|| "*".to_owned(), StreamIds::next);
+        if let Some(message) = pubsub_message.filter(|_| persist && !self.shared_streams.is_empty()) {
+            let fields = [(SHARED_STREAM_FIELD.to_owned(), message)];
+            for stream in &self.shared_streams {
+                commands.push((Cow::Borrowed(stream.as_str()), self.stream_trim.xadd(stream, &id, &fields)));
+            }
+        }
+        let stream_message = self
+            .streams
+            .as_ref()
+            .filter(|_| persist)
+            .and_then(|(streams, encoder)| Some((streams, self.encode_isolated("stream", || encoder.encode(record))?)));
+        if let Some((streams, message)) = stream_message {
+            if let Some(observer) = &self.size_observer {
+                observer(message.iter().map(|(field, value)| field.len() + value.len()).sum());
+            }
+            if wants_fallback && fallback.is_none() {
+                let fields: Vec<Vec<u8>> = message
+                    .iter()
+                    .map(|(field, value)| [field.as_bytes(), b"=", value].concat())
+                    .collect();
+                *fallback = Some(fields.join(&b' '));
+            }
+            let message = message.as_slice();
+            let streams = self.level_streams.get(&record.level()).unwrap_or(streams);
+            for stream in streams {
+                commands.push((Cow::Borrowed(stream.as_str()), self.stream_trim.xadd(stream, &id, message)));
+            }
+            #[cfg(feature = "dated_streams")]
+            if let Some(dated_stream) = &self.dated_stream {
+                let stream = dated_stream.name();
+                let xadd = self.stream_trim.xadd(&stream, &id, message);
+                commands.push((Cow::Owned(stream), xadd));
+            }
+        }
+        for (streams, encoder) in self.multi_streams.iter().filter(|_| persist) {
+            if let Some(message) = self.encode_isolated("stream", || encoder.encode(record)) {
+                if let Some(observer) = &self.size_observer {
+                    observer(message.iter().map(|(field, value)| field.len() + value.len()).sum());
+                }
                 for stream in streams {
-                    pipe.xadd(stream, "*", message);
+                    commands.push((Cow::Borrowed(stream.as_str()), self.stream_trim.xadd(stream, &id, &message)));
+                }
+            }
+        }
+        if commands.len() > first_stream {
+            if let Some(min_id) = self.stream_trim.due_min_id() {
+                for index in first_stream..commands.len() {
+                    let stream = commands[index].0.clone();
+                    let xtrim = self.stream_trim.xtrim(&stream, &min_id);
+                    commands.push((stream, xtrim));
+                }
+            }
+            if let (Some((replicas, timeout)), Some((stream, _))) = (self.wait_replicas, commands.last()) {
+                let mut wait = redis::cmd("WAIT");
+                wait.arg(replicas).arg(timeout.as_millis().to_string());
+                commands.push((stream.clone(), wait));
+            }
+        }
+        for queue in &self.queues {
+            if let Some(message) = self.encode_isolated("queue", || (queue.encoder)(record)) {
+                let mut lpush = redis::cmd("LPUSH");
+                lpush.arg(&queue.list).arg(message);
+                commands.push((Cow::Borrowed(queue.list.as_str()), lpush));
+            }
+        }
+        for custom in &self.custom_commands {
+            if let Some(fields) = self.encode_isolated("custom command", || custom.encoder.encode(record)) {
+                for key in &custom.keys {
+                    let mut command = redis::cmd(&custom.name);
+                    command.arg(key);
+                    for (field, value) in &fields {
+                        command.arg(field).arg(value);
+                    }
+                    commands.push((Cow::Borrowed(key.as_str()), command));
                 }
             }
+        }
+        #[cfg(feature = "time_series")]
+        for target in &self.time_series {
+            if let Some((key, command)) = target.command(record) {
+                commands.push((Cow::Owned(key), command));
+            }
+        }
+        commands
+    }
 
-            // this unwrap only panics if the connection is poisoned, so we can't do much anyway and will panic, too!
-            if let Err(e) = pipe.query::<()>(&mut config.connection.lock().unwrap()) {
-                eprintln!("Error logging to Redis: {e}");
+    /// Passes the commands to the command observer, or prints them to stderr in a dry run without one.
+    fn observe(&self, commands: &[Command<'_>]) {
+        for command in commands {
+            let command = ObservedCommand::new(command);
+            match &self.command_observer {
+                Some(observer) => observer(&command),
+                None => eprintln!("redis_logger dry run: {command}"),
+            }
+        }
+    }
+
+    /// Sends the commands either in pipelines of at most `max_pipeline_commands` commands or, with independent targets,
+    /// one query per target, after passing them to the command observer. In a dry run, they are only observed. Returns
+    /// `false` if any of the commands could not be written.
+    fn send(&self, commands: &[Command<'_>]) -> bool {
+        if self.command_observer.is_some() || self.dry_run {
+            self.observe(commands);
+            if self.dry_run {
+                return true;
+            }
+        }
+        let mut written = true;
+        if self.independent_targets {
+            for command in commands {
+                written &= self.execute(std::slice::from_ref(command));
+            }
+        } else {
+            for chunk in commands.chunks(self.max_pipeline_commands) {
+                written &= self.execute(chunk);
+            }
+        }
+        written
+    }
+
+    /// Sends the commands to Redis in one pipeline, retrying transient errors up to `max_retries` times.
+    /// Commands that still fail with a transient error are kept in the retry buffer, if one is configured.
+    /// Returns `false` if the commands could not be written.
+    fn execute(&self, commands: &[Command<'_>]) -> bool {
+        if commands.is_empty() {
+            return true;
+        }
+        // a single command, e.g. a `PUBLISH` to the only channel, is sent as it is packed without copying it into a pipeline
+        let packed = match commands {
+            [(_, command)] => command.get_packed_command(),
+            _ => commands
+                .iter()
+                .map(|(_, command)| command.get_packed_command())
+                .collect::<Vec<_>>()
+                .concat(),
+        };
+        let target = if self.independent_targets {
+            Some(commands[0].0.to_string())
+        } else {
+            None
+        };
+
+        // this unwrap only panics if the connection is poisoned, so we can't do much anyway and will panic, too!
+        let mut connection = self.connection.lock().unwrap();
+        let mut errors = Vec::new();
+        let replayed = self.replay_buffered(&mut *connection, &mut errors);
+        if !replayed || !errors.is_empty() {
+            // the error handler may log through this logger, so errors are only reported with the connection unlocked
+            drop(connection);
+            errors.into_iter().for_each(|error| self.report(error));
+            if !replayed {
+                self.buffer(commands);
+                if self.count_failure() {
+                    self.report_disabled();
+                }
+                return false;
+            }
+            connection = self.connection.lock().unwrap();
+        }
+        let mut attempts = 0;
+        loop {
+            let replies = if self.per_command_results && commands.len() > 1 {
+                req_per_command(&mut *connection, &packed, commands.len())
+            } else {
+                connection
+                    .req_packed_commands(&packed, 0, commands.len())
+                    .map(|replies| replies.into_iter().map(Ok).collect())
+            };
+            match replies {
+                Ok(replies) => {
+                    drop(connection);
+                    *self.last_error.lock().unwrap() = None;
+                    if let Some(budget) = &self.retry_budget {
+                        budget.failures.store(0, Ordering::Relaxed);
+                    }
+                    if let Some(fallback) = &self.stderr_fallback {
+                        fallback.connected.store(true, Ordering::Relaxed);
+                    }
+                    self.warn_on_no_subscribers(commands, &replies);
+                    return self.handle_failed_commands(commands, replies);
+                }
+                Err(error) => {
+                    if self.stream_ids.is_some() && is_stream_id_rejected(&error) {
+                        if let Some(fallback) = with_auto_stream_ids(commands) {
+                            drop(connection);
+                            return self.execute(&fallback);
+                        }
+                    }
+                    // the reply to a timed out command may still arrive and would be read as the reply to the next one,
+                    // so the connection is replaced if possible; if connecting fails, the old one is kept. The new one
+                    // is created unlocked, as connecting may report errors to a handler logging through this logger.
+                    if error.is_timeout() && self.connect.is_some() {
+                        drop(connection);
+                        let new_connection = self.new_connection();
+                        connection = self.connection.lock().unwrap();
+                        if let Ok(new_connection) = new_connection {
+                            *connection = new_connection;
+                        }
+                    }
+                    let retryable = is_retryable(&error);
+                    let disabled = self.count_failure();
+                    if retryable && attempts < self.max_retries && !disabled {
+                        attempts += 1;
+                    } else {
+                        drop(connection);
+                        if retryable {
+                            self.buffer(commands);
+                        }
+                        self.report(RedisLoggerError::Redis {
+                            error,
+                            retryable,
+                            target,
+                        });
+                        if disabled {
+                            self.report_disabled();
+                        }
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reports the commands whose reply is an error with their target, only possible with `with_per_command_results`.
+    /// Commands failing with a transient error are kept in the retry buffer, if one is configured.
+    /// Returns `false` if any of the commands failed.
+    fn handle_failed_commands(&self, commands: &[Command<'_>], replies: Vec<redis::RedisResult<redis::Value>>) -> bool {
+        let mut written = true;
+        let mut retry = Vec::new();
+        for (command, reply) in commands.iter().zip(replies) {
+            let Err(error) = reply else {
+                continue;
+            };
+            if self.stream_ids.is_some() && is_stream_id_rejected(&error) {
+                if let Some(fallback) = with_auto_stream_ids(std::slice::from_ref(command)) {
+                    written &= self.execute(&fallback);
+                    continue;
+                }
+            }
+            written = false;
+            let retryable = is_retryable(&error);
+            if retryable {
+                retry.push(command.clone());
             }
+            self.report(RedisLoggerError::Redis {
+                error,
+                retryable,
+                target: Some(command.0.to_string()),
+            });
         }
+        if !retry.is_empty() {
+            self.buffer(&retry);
+        }
+        written
+    }
+
+    /// Reports the channels of `PUBLISH` commands whose reply counts no subscribers, if enabled and not reported recently.
+    fn warn_on_no_subscribers(&self, commands: &[Command<'_>], replies: &[redis::RedisResult<redis::Value>]) {
+        let Some(last) = &self.no_subscribers_warning else {
+            return;
+        };
+        let channels: Vec<&str> = commands
+            .iter()
+            .zip(replies)
+            .filter(|((_, command), reply)| {
+                matches!(command.args_iter().next(), Some(redis::Arg::Simple(b"PUBLISH")))
+                    && matches!(reply, Ok(redis::Value::Int(0)))
+            })
+            .map(|((channel, _), _)| channel.as_ref())
+            .collect();
+        if channels.is_empty() {
+            return;
+        }
+        {
+            let mut last = last.lock().unwrap();
+            if last.is_some_and(|last| last.elapsed() < NO_SUBSCRIBERS_WARNING_INTERVAL) {
+                return;
+            }
+            *last = Some(Instant::now());
+        }
+        for channel in channels {
+            self.report(RedisLoggerError::NoSubscribers {
+                channel: channel.to_owned(),
+            });
+        }
+    }
+
+    /// Sends the commands in the disk spool and the retry buffer before any new ones to keep them in order.
+    /// Returns `false` if the buffered commands could not be sent due to a transient error and have been kept.
+    /// Buffered commands failing with a permanent error are discarded as they would never succeed.
+    /// Errors are added to `errors` for the caller to report once the connection is unlocked.
+    fn replay_buffered(&self, connection: &mut CONN, errors: &mut Vec<RedisLoggerError>) -> bool {
+        if !self.replay_spool(connection, errors) {
+            return false;
+        }
+        let Some(buffer) = &self.retry_buffer else {
+            return true;
+        };
+        let mut records = buffer.records.lock().unwrap();
+        if records.is_empty() {
+            return true;
+        }
+        let packed: Vec<u8> = records.iter().flat_map(|record| record.command.iter().copied()).collect();
+        match connection.req_packed_commands(&packed, 0, records.len()) {
+            Ok(_) => {
+                records.clear();
+                true
+            }
+            Err(error) => {
+                let retryable = is_retryable(&error);
+                if !retryable {
+                    records.clear();
+                }
+                errors.push(RedisLoggerError::Redis {
+                    error,
+                    retryable,
+                    target: None,
+                });
+                !retryable
+            }
+        }
+    }
+
+    /// Sends the commands in the disk spool in pipelines of at most `max_pipeline_commands` commands and removes the sent
+    /// ones from the spool. Returns `false` if a transient error occurred, the remaining commands are kept in the spool then.
+    /// Errors are added to `errors` for the caller to report once the connection is unlocked.
+    fn replay_spool(&self, connection: &mut CONN, errors: &mut Vec<RedisLoggerError>) -> bool {
+        let Some(spool) = &self.disk_spool else {
+            return true;
+        };
+        let commands = match spool.read() {
+            Ok(commands) if commands.is_empty() => return true,
+            Ok(commands) => commands,
+            Err(error) => {
+                errors.push(spool_error(spool, error));
+                return true;
+            }
+        };
+        let mut done = 0;
+        for chunk in commands.chunks(self.max_pipeline_commands) {
+            if let Err(error) = connection.req_packed_commands(&chunk.concat(), 0, chunk.len()) {
+                let retryable = is_retryable(&error);
+                errors.push(RedisLoggerError::Redis {
+                    error,
+                    retryable,
+                    target: None,
+                });
+                if retryable {
+                    break;
+                }
+            }
+            done += chunk.len();
+        }
+        if let Err(error) = spool.replace(&commands[done..]) {
+            errors.push(spool_error(spool, error));
+        }
+        done == commands.len()
+    }
+
+    /// Adds the commands to the disk spool if configured, or to the retry buffer, evicting the oldest ones if the buffer is full.
+    fn buffer(&self, commands: &[Command<'_>]) {
+        if let Some(spool) = &self.disk_spool {
+            let packed: Vec<Vec<u8>> = commands.iter().map(|(_, command)| command.get_packed_command()).collect();
+            if let Err(error) = spool.append(&packed) {
+                self.count_dropped(DropReason::SpoolFailed, packed.len() as u64);
+                self.report(spool_error(spool, error));
+            }
+            return;
+        }
+        let Some(buffer) = &self.retry_buffer else {
+            return;
+        };
+        let timestamp = SystemTime::now();
+        let mut records = buffer.records.lock().unwrap();
+        for (target, command) in commands {
+            if records.len() == buffer.capacity {
+                records.pop_front();
+                self.count_dropped(DropReason::RetryBufferFull, 1);
+            }
+            records.push_back(BufferedRecord {
+                target: target.to_string(),
+                command: command.get_packed_command(),
+                timestamp,
+            });
+        }
+    }
+
+    fn flush(&self) {
+        if self.is_disabled() {
+            return;
+        }
+        let mut errors = Vec::new();
+        // this unwrap only panics if the connection is poisoned, so we can't do much anyway and will panic, too!
+        self.replay_buffered(&mut *self.connection.lock().unwrap(), &mut errors);
+        errors.into_iter().for_each(|error| self.report(error));
+    }
+
+    fn take_buffered(&self) -> Vec<BufferedRecord> {
+        self.retry_buffer
+            .as_ref()
+            .map(|buffer| std::mem::take(&mut *buffer.records.lock().unwrap()).into())
+            .unwrap_or_default()
+    }
+
+    /// Writes the line stored by `commands` to stderr if the stderr fallback is enabled and Redis has not been reachable
+    /// so far.
+    fn write_fallback(&self, line: Option<Vec<u8>>) {
+        let (Some(fallback), Some(mut line)) = (&self.stderr_fallback, line) else {
+            return;
+        };
+        if fallback.connected.load(Ordering::Relaxed) {
+            return;
+        }
+        line.push(b'\n');
+        // stderr is the last resort, so there is nowhere to report a failed write to
+        let _ = fallback.writer.lock().unwrap().write_all(&line);
     }
 
-    fn flush(&self) {}
+    fn set_last_error(&self, error: &dyn fmt::Display) {
+        *self.last_error.lock().unwrap() = Some((SystemTime::now(), error.to_string()));
+    }
+
+    fn report(&self, mut error: RedisLoggerError) {
+        if let RedisLoggerError::Redis { .. } = error {
+            self.set_last_error(&error);
+        }
+        if let Some(throttle) = &self.error_throttle {
+            let mut state = throttle.state.lock().unwrap();
+            let (last, suppressed) = &mut *state;
+            if last.is_some_and(|last| last.elapsed() < throttle.interval) {
+                *suppressed += 1;
+                return;
+            }
+            *last = Some(Instant::now());
+            if *suppressed > 0 {
+                error = RedisLoggerError::Throttled {
+                    error: Box::new(error),
+                    suppressed: std::mem::take(suppressed),
+                };
+            }
+            // unlocked before calling the handler, which may log through this logger and report further errors
+            drop(state);
+        }
+        match &self.error_handler {
+            Some(handler) => handler(&error),
+            None => eprintln!("{error}"),
+        }
+    }
 }
 
-/// Configuration for the Redis logger. Pass to `RedisLogger` to configure the logger.
-#[derive(Debug)]
-pub struct RedisLoggerConfig<CONN, PUBSUB, STREAM>
+impl<CONN, PUBSUB, STREAM> Drop for RedisLoggerConfig<CONN, PUBSUB, STREAM>
 where
     CONN: ConnectionLike + Send + Sync,
     PUBSUB: PubSubEncoder,
     STREAM: StreamEncoder,
 {
-    connection: Mutex<CONN>,
-    channels: Option<(Vec<String>, PUBSUB)>,
-    streams: Option<(Vec<String>, STREAM)>,
+    /// Counts the connection as closed for `live_connections`. Background threads only hold it weakly and let it go.
+    fn drop(&mut self) {
+        LIVE_CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The connection is left out of the `Debug` output on purpose, as connections (or the clients they are created from)
+/// may print connection details including passwords.
+impl<CONN, PUBSUB, STREAM> fmt::Debug for RedisLoggerConfig<CONN, PUBSUB, STREAM>
+where
+    CONN: ConnectionLike + Send + Sync,
+    PUBSUB: PubSubEncoder + fmt::Debug,
+    STREAM: StreamEncoder + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("RedisLoggerConfig");
+        f.field("channels", &self.channels)
+            .field("streams", &self.streams)
+            .field("max_retries", &self.max_retries)
+            .field("error_handler", &self.error_handler.is_some())
+            .field("independent_targets", &self.independent_targets)
+            .field("per_command_results", &self.per_command_results)
+            .field("retry_buffer", &self.retry_buffer)
+            .field("wait_replicas", &self.wait_replicas)
+            .field("key_prefix", &self.key_prefix)
+            .field("level_streams", &self.level_streams)
+            .field("stream_trim", &self.stream_trim)
+            .field("background_trim", &self.background_trim)
+            .field("idle_heartbeat", &self.idle_heartbeat)
+            .field("max_pipeline_commands", &self.max_pipeline_commands)
+            .field("remote_level", &self.remote_level)
+            .field("stderr_fallback", &self.stderr_fallback)
+            .field("shared_streams", &self.shared_streams)
+            .field("ignored_targets", &self.ignored_targets)
+            .field("disk_spool", &self.disk_spool)
+            .field("connect", &self.connect.is_some())
+            .field("client_name", &self.client_name)
+            .field("connection_info", &self.connection_info)
+            .field("target_sampling", &self.target_sampling)
+            .field("dedup_ttl", &self.dedup_ttl)
+            .field("drop_markers", &self.drop_markers)
+            .field("stream_ids", &self.stream_ids)
+            .field("routing_key", &self.routing_key)
+            .field("retry_budget", &self.retry_budget)
+            .field("size_observer", &self.size_observer.is_some())
+            .field("command_observer", &self.command_observer.is_some())
+            .field("dry_run", &self.dry_run)
+            .field("queues", &self.queues)
+            .field("custom_commands", &self.custom_commands)
+            .field(
+                "channel_encoders",
+                &self.channel_encoders.iter().map(|(channels, _)| channels).collect::<Vec<_>>(),
+            )
+            .field("dynamic_channels", &self.dynamic_channels.is_some())
+            .field("sharded_channels", &self.sharded_channels)
+            .field("alert_channel", &self.alert_channel)
+            .field("scripted_fan_out", &self.scripted_fan_out)
+            .field(
+                "multi_streams",
+                &self.multi_streams.iter().map(|(streams, _)| streams).collect::<Vec<_>>(),
+            )
+            .field("no_subscribers_warning", &self.no_subscribers_warning.is_some())
+            .field(
+                "error_throttle",
+                &self.error_throttle.as_ref().map(|throttle| throttle.interval),
+            );
+        #[cfg(feature = "dated_streams")]
+        f.field("dated_stream", &self.dated_stream);
+        #[cfg(feature = "time_series")]
+        f.field("time_series", &self.time_series);
+        #[cfg(feature = "compression")]
+        f.field("compressed_channel", &self.compressed_channel);
+        f.finish_non_exhaustive()
+    }
 }
 
 /// `RedisLoggerConfigBuilder` is a builder for `RedisLoggerConfig`.
@@ -280,11 +3325,7 @@ impl RedisLoggerConfigBuilder {
         PUBSUB: PubSubEncoder,
     {
         Self::check_args(!channels.is_empty());
-        RedisLoggerConfig {
-            connection: Mutex::new(connection),
-            channels: Some((channels, encoder)),
-            streams: None,
-        }
+        RedisLoggerConfig::new(connection, Some((channels, encoder)), None)
     }
 
     /// Constructs a `RedisLoggerConfig` with a given connection and channels, using the default Pub/Sub encoder.
@@ -312,11 +3353,62 @@ impl RedisLoggerConfigBuilder {
         CONN: ConnectionLike + Send + Sync,
     {
         Self::check_args(!channels.is_empty());
-        RedisLoggerConfig {
-            connection: Mutex::new(connection),
-            channels: Some((channels, DefaultPubSubEncoder::new())),
-            streams: None,
-        }
+        RedisLoggerConfig::new(connection, Some((channels, DefaultPubSubEncoder::new())), None)
+    }
+
+    /// Constructs a `RedisLoggerConfig` with a given connection and channels, using the `EcsPubSubEncoder`.
+    ///
+    /// This method is only available when the `ecs` feature is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - A connection that implements `ConnectionLike + Send + Sync`.
+    /// * `channels` - A vector of channel names.
+    ///
+    /// # Returns
+    ///
+    /// A `RedisLoggerConfig` with the given connection and channels, and the ECS Pub/Sub encoder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channels vector is empty
+    #[cfg_attr(docsrs, doc(cfg(feature = "ecs")))]
+    #[cfg(feature = "ecs")]
+    pub fn build_with_pubsub_ecs<CONN>(
+        connection: CONN,
+        channels: Vec<String>,
+    ) -> RedisLoggerConfig<CONN, EcsPubSubEncoder, DummyStreamEncoder>
+    where
+        CONN: ConnectionLike + Send + Sync,
+    {
+        Self::check_args(!channels.is_empty());
+        RedisLoggerConfig::new(connection, Some((channels, EcsPubSubEncoder::new())), None)
+    }
+
+    /// Constructs a `RedisLoggerConfig` with a given connection and channels, using the `CsvPubSubEncoder` with its
+    /// default columns `level,target,message,file,line`.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - A connection that implements `ConnectionLike + Send + Sync`.
+    /// * `channels` - A vector of channel names.
+    ///
+    /// # Returns
+    ///
+    /// A `RedisLoggerConfig` with the given connection and channels, and the CSV Pub/Sub encoder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channels vector is empty
+    pub fn build_with_pubsub_csv<CONN>(
+        connection: CONN,
+        channels: Vec<String>,
+    ) -> RedisLoggerConfig<CONN, CsvPubSubEncoder, DummyStreamEncoder>
+    where
+        CONN: ConnectionLike + Send + Sync,
+    {
+        Self::check_args(!channels.is_empty());
+        RedisLoggerConfig::new(connection, Some((channels, CsvPubSubEncoder::new())), None)
     }
 
     /// Constructs a `RedisLoggerConfig` with a given connection, streams, and a Stream encoder.
@@ -344,11 +3436,7 @@ impl RedisLoggerConfigBuilder {
         STREAM: StreamEncoder,
     {
         Self::check_args(!streams.is_empty());
-        RedisLoggerConfig {
-            connection: Mutex::new(connection),
-            channels: None,
-            streams: Some((streams, encoder)),
-        }
+        RedisLoggerConfig::new(connection, None, Some((streams, encoder)))
     }
 
     /// Constructs a `RedisLoggerConfig` with a given connection and streams, using the default Stream encoder.
@@ -376,11 +3464,42 @@ impl RedisLoggerConfigBuilder {
         CONN: ConnectionLike + Send + Sync,
     {
         Self::check_args(!streams.is_empty());
-        RedisLoggerConfig {
-            connection: Mutex::new(connection),
-            channels: None,
-            streams: Some((streams, DefaultStreamEncoder::new())),
-        }
+        RedisLoggerConfig::new(connection, None, Some((streams, DefaultStreamEncoder::new())))
+    }
+
+    /// Constructs a `RedisLoggerConfig` with a given connection, channels, streams and one Pub/Sub encoder shared by both.
+    ///
+    /// Every record is encoded once. The bytes are published to the channels and added to the streams as the single
+    /// field `message`, so live subscribers and the persisted streams get identical content without encoding twice.
+    ///
+    /// # Arguments
+    ///
+    /// * `connection` - A connection that implements `ConnectionLike + Send + Sync`.
+    /// * `channels` - A vector of channel names.
+    /// * `streams` - A vector of stream names.
+    /// * `encoder` - An encoder that implements `PubSubEncoder`, used for both channels and streams.
+    ///
+    /// # Returns
+    ///
+    /// A `RedisLoggerConfig` with the given connection, channels, streams and shared encoder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the streams or channels vectors are empty
+    pub fn build_with_pubsub_and_stream_shared<CONN, PUBSUB>(
+        connection: CONN,
+        channels: Vec<String>,
+        streams: Vec<String>,
+        encoder: PUBSUB,
+    ) -> RedisLoggerConfig<CONN, PUBSUB, DummyStreamEncoder>
+    where
+        CONN: ConnectionLike + Send + Sync,
+        PUBSUB: PubSubEncoder,
+    {
+        Self::check_args(!channels.is_empty() && !streams.is_empty());
+        let mut config = RedisLoggerConfig::new(connection, Some((channels, encoder)), None);
+        config.shared_streams = streams;
+        config
     }
 
     /// Constructs a `RedisLoggerConfig` with a given connection, channels, streams, a Pub/Sub encoder, and a Stream encoder.
@@ -413,11 +3532,7 @@ impl RedisLoggerConfigBuilder {
         STREAM: StreamEncoder,
     {
         Self::check_args(!channels.is_empty() && !streams.is_empty());
-        RedisLoggerConfig {
-            connection: Mutex::new(connection),
-            channels: Some((channels, pubsub_encoder)),
-            streams: Some((streams, stream_encoder)),
-        }
+        RedisLoggerConfig::new(connection, Some((channels, pubsub_encoder)), Some((streams, stream_encoder)))
     }
 
     /// Constructs a `RedisLoggerConfig` with a given connection, channels, and streams, using the default Pub/Sub and Stream encoders.
@@ -447,11 +3562,11 @@ impl RedisLoggerConfigBuilder {
         CONN: ConnectionLike + Send + Sync,
     {
         Self::check_args(!channels.is_empty() && !streams.is_empty());
-        RedisLoggerConfig {
-            connection: Mutex::new(connection),
-            channels: Some((channels, DefaultPubSubEncoder::new())),
-            streams: Some((streams, DefaultStreamEncoder::new())),
-        }
+        RedisLoggerConfig::new(
+            connection,
+            Some((channels, DefaultPubSubEncoder::new())),
+            Some((streams, DefaultStreamEncoder::new())),
+        )
     }
 
     const fn check_args(value: bool) {
@@ -471,7 +3586,7 @@ where
     STREAM: StreamEncoder + 'static,
 {
     fn level(&self) -> log::LevelFilter {
-        self.level
+        self.level.get()
     }
 
     fn config(&self) -> Option<&simplelog::Config> {
@@ -0,0 +1,53 @@
+//! # Macros Module
+//!
+//! This module provides the `stream_encoder!` macro to generate `StreamEncoder` implementations.
+
+/// Generates a `StreamEncoder` that converts every record into a struct and emits its fields as stream fields.
+///
+/// The struct must implement `From<&log::Record>`. The macro destructures it without `..`, so forgetting a field (or
+/// naming one that doesn't exist) is a compile error. This keeps the fields of the encoder in sync with the struct.
+/// Every field is encoded with its `Display` implementation and the field name is used as the stream field name.
+///
+/// ```rust
+/// use redis_logger::{stream_encoder, StreamEncoder};
+///
+/// struct AccessLog {
+///     level: log::Level,
+///     path: String,
+/// }
+///
+/// impl From<&log::Record<'_>> for AccessLog {
+///     fn from(record: &log::Record<'_>) -> Self {
+///         Self {
+///             level: record.level(),
+///             path: record.args().to_string(),
+///         }
+///     }
+/// }
+///
+/// stream_encoder! {
+///     /// Encodes records as access logs.
+///     pub struct AccessLogEncoder for AccessLog { level, path }
+/// }
+///
+/// let record = log::Record::builder().level(log::Level::Info).args(format_args!("/index.html")).build();
+/// assert_eq!(
+///     AccessLogEncoder.encode(&record),
+///     vec![("level".to_owned(), b"INFO".to_vec()), ("path".to_owned(), b"/index.html".to_vec())]
+/// );
+/// ```
+#[macro_export]
+macro_rules! stream_encoder {
+    ($(#[$meta:meta])* $vis:vis struct $encoder:ident for $record:ident { $($field:ident),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default)]
+        $vis struct $encoder;
+
+        impl $crate::StreamEncoder for $encoder {
+            fn encode(&self, record: &::log::Record) -> ::std::vec::Vec<(::std::string::String, ::std::vec::Vec<u8>)> {
+                let $record { $($field),+ } = $record::from(record);
+                ::std::vec![$((::std::string::String::from(::std::stringify!($field)), ::std::string::ToString::to_string(&$field).into_bytes())),+]
+            }
+        }
+    };
+}
@@ -0,0 +1,51 @@
+//! # Signal Module
+//!
+//! This module provides `install_flush_signal_handler` to flush the global logger when the process receives a Unix signal.
+
+use std::{
+    io,
+    os::raw::c_int,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+};
+
+use signal_hook::iterator::Signals;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Flushes the global logger (`log::logger()`) every time the process receives `signal`, e.g. `signal_hook::consts::SIGUSR1`.
+///
+/// Operators can then send the buffered records of a `RedisLogger` (see `RedisLoggerConfig::with_retry_buffer`) to Redis
+/// with `kill -USR1 <pid>`, without the application calling `flush` itself. The logger must be the global logger, i.e. set
+/// with `RedisLogger::init` or wrapped by a global logger that forwards `flush`.
+///
+/// The signal is handled on a background thread, so flushing does not run in the restricted context of a signal handler.
+/// Only Unix signals are supported, and signals that can't be handled like `SIGKILL` or `SIGSTOP` are rejected.
+///
+/// # Errors
+///
+/// Returns an error if the handler has already been installed, as a second handler would flush twice per signal,
+/// or if the signal can't be handled.
+pub fn install_flush_signal_handler(signal: c_int) -> io::Result<()> {
+    if INSTALLED.swap(true, Ordering::SeqCst) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "the flush signal handler is already installed",
+        ));
+    }
+    let mut signals = match Signals::new([signal]) {
+        Ok(signals) => signals,
+        Err(error) => {
+            INSTALLED.store(false, Ordering::SeqCst);
+            return Err(error);
+        }
+    };
+    thread::Builder::new()
+        .name("redis_logger-flush-signal".to_owned())
+        .spawn(move || {
+            for _ in signals.forever() {
+                log::logger().flush();
+            }
+        })?;
+    Ok(())
+}
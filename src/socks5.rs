@@ -0,0 +1,287 @@
+//! # SOCKS5 Module
+//!
+//! This module provides `StreamConnection`, a `ConnectionLike` implementation on top of any byte stream, and a helper
+//! to open such a stream through a SOCKS5 proxy.
+
+use std::{
+    convert::TryFrom,
+    fmt,
+    io::{self, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use redis::{ConnectionLike, Parser, RedisError, RedisResult, Value};
+
+/// A connection to Redis over a caller-supplied stream, e.g. a `TcpStream` tunneled through a SOCKS5 proxy.
+///
+/// `redis::Connection` can only be created by `redis` itself from a `ConnectionInfo`, so it can't wrap a custom stream.
+/// `StreamConnection` fills this gap: it writes the packed commands to the stream and parses the replies with
+/// `redis::Parser`, which is all `RedisLogger` needs from a `ConnectionLike`.
+///
+/// Keep the tradeoffs in mind:
+/// - There is no reconnect. If the stream breaks, every write fails until a new logger is created.
+/// - Nothing is sent on connect. Authenticate and select the database yourself (e.g. `redis::cmd("AUTH").arg(...).query(&mut connection)`)
+///   before passing the connection to the `RedisLoggerConfigBuilder`.
+/// - TLS is not handled. Wrap the stream in a TLS stream yourself if the server requires it.
+///
+/// ```no_run
+/// # use redis_logger::StreamConnection;
+/// let connection = StreamConnection::connect_socks5("proxy.internal:1080", "redis.internal", 6379, None).unwrap();
+/// // pass the connection to any of the `RedisLoggerConfigBuilder` methods
+/// ```
+pub struct StreamConnection<S: Read + Write> {
+    reader: BufReader<S>,
+    parser: Parser,
+    db: i64,
+    open: bool,
+}
+
+impl<S: Read + Write> StreamConnection<S> {
+    /// Creates a connection communicating over the given stream. The stream must already be connected to Redis.
+    pub fn new(stream: S) -> Self {
+        Self {
+            reader: BufReader::new(stream),
+            parser: Parser::new(),
+            db: 0,
+            open: true,
+        }
+    }
+
+    /// Sets the database reported by `ConnectionLike::get_db`. This does not send a `SELECT`. Defaults to 0.
+    #[must_use]
+    pub const fn with_db(mut self, db: i64) -> Self {
+        self.db = db;
+        self
+    }
+
+    fn read_response(&mut self) -> RedisResult<Value> {
+        let response = self.parser.parse_value(&mut self.reader);
+        if matches!(&response, Err(error) if error.is_io_error() || error.is_connection_dropped()) {
+            self.open = false;
+        }
+        response
+    }
+
+    fn send_bytes(&mut self, bytes: &[u8]) -> RedisResult<()> {
+        let stream = self.reader.get_mut();
+        let result = stream.write_all(bytes).and_then(|()| stream.flush());
+        if result.is_err() {
+            self.open = false;
+        }
+        Ok(result?)
+    }
+}
+
+impl<S: Read + Write> fmt::Debug for StreamConnection<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamConnection")
+            .field("db", &self.db)
+            .field("open", &self.open)
+            .finish_non_exhaustive()
+    }
+}
+
+impl StreamConnection<TcpStream> {
+    /// Connects to the Redis server at `host:port` through the SOCKS5 proxy at `proxy`.
+    ///
+    /// Only the "no authentication" method of SOCKS5 is supported. The host name is resolved by the proxy, so it only needs
+    /// to be resolvable inside the restricted network. The `timeout` applies to connecting to the proxy as well as to every
+    /// read and write afterwards. With the feature flag `tcp_nodelay`, `TCP_NODELAY` is set on the socket.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the proxy is not reachable or refuses the connection.
+    pub fn connect_socks5(proxy: impl ToSocketAddrs, host: &str, port: u16, timeout: Option<Duration>) -> RedisResult<Self> {
+        let mut stream = match timeout {
+            Some(timeout) => {
+                let address = proxy
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no proxy address"))?;
+                TcpStream::connect_timeout(&address, timeout)?
+            }
+            None => TcpStream::connect(proxy)?,
+        };
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+        #[cfg(feature = "tcp_nodelay")]
+        stream.set_nodelay(true)?;
+        socks5_handshake(&mut stream, host, port)?;
+        Ok(Self::new(stream))
+    }
+}
+
+/// Asks the SOCKS5 proxy behind `stream` to connect to `host:port` (RFC 1928), without authentication.
+fn socks5_handshake(stream: &mut (impl Read + Write), host: &str, port: u16) -> io::Result<()> {
+    let proxy_error = |message: &str| io::Error::new(io::ErrorKind::ConnectionRefused, format!("SOCKS5 proxy: {message}"));
+
+    stream.write_all(&[5, 1, 0])?;
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply)?;
+    if reply != [5, 0] {
+        return Err(proxy_error("no supported authentication method"));
+    }
+
+    let host_len = u8::try_from(host.len()).map_err(|_| proxy_error("host name too long"))?;
+    let mut request = vec![5, 1, 0, 3, host_len];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request)?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply)?;
+    if reply[1] != 0 {
+        return Err(proxy_error(&format!("connect failed with reply code {}", reply[1])));
+    }
+    // skip the bound address and port, which we don't need
+    let address_len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => {
+            let mut len = [0; 1];
+            stream.read_exact(&mut len)?;
+            usize::from(len[0])
+        }
+        _ => return Err(proxy_error("invalid address type")),
+    };
+    let mut bound = vec![0; address_len + 2];
+    stream.read_exact(&mut bound)?;
+    Ok(())
+}
+
+impl<S: Read + Write> ConnectionLike for StreamConnection<S> {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.send_bytes(cmd)?;
+        self.read_response()
+    }
+
+    fn req_packed_commands(&mut self, cmd: &[u8], offset: usize, count: usize) -> RedisResult<Vec<Value>> {
+        self.send_bytes(cmd)?;
+        let mut values = Vec::with_capacity(count);
+        let mut first_error: Option<RedisError> = None;
+        // read all replies even after an error, otherwise they would be taken as the replies of the next commands
+        for index in 0..offset + count {
+            match self.read_response() {
+                Ok(value) if index >= offset => values.push(value),
+                Ok(_) => {}
+                Err(error) => {
+                    if !self.open {
+                        return Err(error);
+                    }
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        first_error.map_or(Ok(values), Err)
+    }
+
+    fn get_db(&self) -> i64 {
+        self.db
+    }
+
+    fn check_connection(&mut self) -> bool {
+        redis::cmd("PING").query::<String>(self).is_ok()
+    }
+
+    fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    /// A stream that reads the given replies and collects everything written to it.
+    struct Duplex {
+        replies: io::Cursor<Vec<u8>>,
+        written: Vec<u8>,
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.replies.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stream_connection_pipeline() {
+        let mut connection = StreamConnection::new(Duplex {
+            replies: io::Cursor::new(b"+OK\r\n:3\r\n-ERR wrong\r\n:4\r\n".to_vec()),
+            written: Vec::new(),
+        });
+
+        let values = connection.req_packed_commands(b"packed", 1, 1).unwrap();
+        assert_eq!(values, vec![Value::Int(3)]);
+        let error = connection.req_packed_command(b"more").unwrap_err();
+        assert_eq!(error.code(), Some("ERR"));
+        assert!(connection.is_open());
+        assert_eq!(connection.req_packed_command(b"last").unwrap(), Value::Int(4));
+        assert_eq!(connection.reader.get_ref().written, b"packedmorelast");
+
+        assert!(connection.req_packed_command(b"eof").is_err());
+        assert!(!connection.is_open());
+    }
+
+    #[test]
+    fn test_stream_connection_reads_replies_one_by_one() {
+        // this is how `with_per_command_results` reads the replies to a pipeline
+        let mut connection = StreamConnection::new(Duplex {
+            replies: io::Cursor::new(b":1\r\n-WRONGTYPE wrong\r\n:3\r\n".to_vec()),
+            written: Vec::new(),
+        });
+
+        assert_eq!(connection.req_packed_commands(b"packed", 0, 0).unwrap(), vec![]);
+        assert_eq!(connection.req_packed_commands(&[], 0, 1).unwrap(), vec![Value::Int(1)]);
+        let error = connection.req_packed_commands(&[], 0, 1).unwrap_err();
+        assert_eq!(error.code(), Some("WRONGTYPE"));
+        assert!(connection.is_open());
+        assert_eq!(connection.req_packed_commands(&[], 0, 1).unwrap(), vec![Value::Int(3)]);
+        assert_eq!(connection.reader.get_ref().written, b"packed");
+    }
+
+    #[test]
+    fn test_connect_socks5() {
+        // a SOCKS5 proxy that accepts one connection to redis.internal:6379 and answers the first command like Redis would
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut greeting = [0; 3];
+            stream.read_exact(&mut greeting).unwrap();
+            assert_eq!(greeting, [5, 1, 0]);
+            stream.write_all(&[5, 0]).unwrap();
+
+            let mut request = [0; 5 + 14 + 2];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(request[..5], [5, 1, 0, 3, 14]);
+            assert_eq!(&request[5..19], b"redis.internal");
+            assert_eq!(u16::from_be_bytes([request[19], request[20]]), 6379);
+            stream.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0x18, 0xeb]).unwrap();
+
+            let mut command = [0; 14];
+            stream.read_exact(&mut command).unwrap();
+            assert_eq!(&command, b"*1\r\n$4\r\nPING\r\n");
+            stream.write_all(b"+PONG\r\n").unwrap();
+        });
+
+        let mut connection =
+            StreamConnection::connect_socks5(proxy, "redis.internal", 6379, Some(Duration::from_secs(5))).unwrap();
+        assert!(connection.check_connection());
+        #[cfg(feature = "tcp_nodelay")]
+        assert!(connection.reader.get_ref().nodelay().unwrap());
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+//! # Spool Module
+//!
+//! This module provides the disk spool that keeps commands across process restarts while Redis is unavailable.
+
+use std::{
+    convert::TryFrom,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// An append-only file of packed commands, each prefixed with its length as a big-endian `u32`.
+#[derive(Debug)]
+pub(crate) struct DiskSpool {
+    path: PathBuf,
+    max_size: u64,
+    lock: Mutex<()>,
+}
+
+impl DiskSpool {
+    /// Opens the spool at `path`. An incomplete last entry, left by a crash during `append`, is cut off, so later entries
+    /// are appended right after the last complete one.
+    pub(crate) fn new(path: PathBuf, max_size: u64) -> Self {
+        if let Ok((_, len)) = Self::read_entries(&path) {
+            if let Ok(file) = OpenOptions::new().write(true).open(&path) {
+                if file.metadata().is_ok_and(|metadata| metadata.len() > len) {
+                    let _ = file.set_len(len);
+                }
+            }
+        }
+        Self {
+            path,
+            max_size,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the commands and syncs them to disk. Fails without writing anything if the spool would exceed `max_size`.
+    pub(crate) fn append(&self, commands: &[Vec<u8>]) -> io::Result<()> {
+        let mut entries = Vec::new();
+        for command in commands {
+            let len =
+                u32::try_from(command.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "command too large"))?;
+            entries.extend_from_slice(&len.to_be_bytes());
+            entries.extend_from_slice(command);
+        }
+
+        let _lock = self.lock.lock().unwrap();
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let size = file.metadata()?.len();
+        if size + entries.len() as u64 > self.max_size {
+            return Err(io::Error::other(format!(
+                "the spool reached its maximum size of {} bytes, dropping the record",
+                self.max_size
+            )));
+        }
+        file.write_all(&entries)?;
+        file.sync_data()
+    }
+
+    /// Reads all commands in the order they were appended.
+    pub(crate) fn read(&self) -> io::Result<Vec<Vec<u8>>> {
+        let _lock = self.lock.lock().unwrap();
+        Self::read_entries(&self.path).map(|(commands, _)| commands)
+    }
+
+    /// Reads all complete entries of the file and returns them together with their total length in bytes.
+    fn read_entries(path: &Path) -> io::Result<(Vec<Vec<u8>>, u64)> {
+        let mut bytes = Vec::new();
+        match File::open(path) {
+            Ok(mut file) => file.read_to_end(&mut bytes)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+            Err(error) => return Err(error),
+        };
+
+        let mut commands = Vec::new();
+        let mut rest = bytes.as_slice();
+        while rest.len() >= 4 {
+            let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let Some(command) = rest.get(4..4 + len) else {
+                break;
+            };
+            commands.push(command.to_vec());
+            rest = &rest[4 + len..];
+        }
+        Ok((commands, (bytes.len() - rest.len()) as u64))
+    }
+
+    /// Replaces the spool with the given commands. The new content is written to a temporary file first, so a crash
+    /// leaves either the old or the new content.
+    pub(crate) fn replace(&self, commands: &[Vec<u8>]) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        if commands.is_empty() {
+            return match fs::remove_file(&self.path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+                _ => Ok(()),
+            };
+        }
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        let mut file = File::create(&temp)?;
+        for command in commands {
+            file.write_all(&u32::try_from(command.len()).unwrap_or(u32::MAX).to_be_bytes())?;
+            file.write_all(command)?;
+        }
+        file.sync_data()?;
+        fs::rename(&temp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spool_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("redis_logger_{name}_{}.spool", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_spool_round_trip() {
+        let spool = DiskSpool::new(spool_path("round_trip"), 1024);
+        assert!(spool.read().unwrap().is_empty());
+
+        spool.append(&[b"first".to_vec(), b"second".to_vec()]).unwrap();
+        spool.append(&[b"third".to_vec()]).unwrap();
+        assert_eq!(
+            spool.read().unwrap(),
+            vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]
+        );
+
+        spool.replace(&[b"third".to_vec()]).unwrap();
+        assert_eq!(spool.read().unwrap(), vec![b"third".to_vec()]);
+        spool.replace(&[]).unwrap();
+        assert!(!spool.path().exists());
+    }
+
+    #[test]
+    fn test_spool_ignores_partial_write() {
+        let spool = DiskSpool::new(spool_path("partial"), 1024);
+        spool.append(&[b"complete".to_vec()]).unwrap();
+        let mut file = OpenOptions::new().append(true).open(spool.path()).unwrap();
+        file.write_all(&[0, 0, 0, 10, b'p', b'a']).unwrap();
+
+        assert_eq!(spool.read().unwrap(), vec![b"complete".to_vec()]);
+
+        let spool = DiskSpool::new(spool.path().to_owned(), 1024);
+        spool.append(&[b"next".to_vec()]).unwrap();
+        assert_eq!(spool.read().unwrap(), vec![b"complete".to_vec(), b"next".to_vec()]);
+        fs::remove_file(spool.path()).unwrap();
+    }
+
+    #[test]
+    fn test_spool_max_size() {
+        let spool = DiskSpool::new(spool_path("max_size"), 20);
+        spool.append(&[b"0123456789".to_vec()]).unwrap();
+        assert!(spool.append(&[b"0123456789".to_vec()]).is_err());
+        assert_eq!(spool.read().unwrap().len(), 1);
+        fs::remove_file(spool.path()).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+//! # Testing Module
+//!
+//! This module provides `CapturingLogger`, which runs a `RedisLogger` against an in-memory connection and reads back the
+//! commands it sent, so logging configurations can be unit tested without a Redis server.
+
+use std::{
+    str,
+    sync::{Arc, Mutex},
+};
+
+use log::LevelFilter;
+use redis::{ConnectionLike, RedisResult, Value};
+
+use super::{PubSubEncoder, RedisLogger, RedisLoggerConfig, StreamEncoder};
+
+/// A command sent by a logger, decoded by `CaptureHandle::drain`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CapturedCommand {
+    /// A `PUBLISH` of `message` to `channel`.
+    Publish { channel: String, message: Vec<u8> },
+    /// An `XADD` of an entry with `fields` to `stream`. `id` is `*` unless custom stream IDs are enabled, trim options are
+    /// left out.
+    XAdd {
+        stream: String,
+        id: String,
+        fields: Vec<(String, Vec<u8>)>,
+    },
+    /// Any other command with its arguments, starting with the command name, e.g. `LPUSH` or `SET`.
+    Other(Vec<Vec<u8>>),
+}
+
+/// `CapturingLogger` creates a `RedisLogger` writing to a `CapturingConnection` instead of Redis, together with a
+/// `CaptureHandle` to read back what the logger sent. Use it to test encoders and routing without a Redis server.
+///
+/// ```rust
+/// # use redis_logger::{CapturedCommand, CapturingLogger, PubSubEncoder, RedisLoggerConfigBuilder};
+/// # use log::{Log, Record};
+/// struct MessageEncoder;
+///
+/// impl PubSubEncoder for MessageEncoder {
+///     fn encode(&self, record: &Record) -> Vec<u8> {
+///         record.args().to_string().into_bytes()
+///     }
+/// }
+///
+/// let (logger, handle) = CapturingLogger::new(log::LevelFilter::Info, |connection| {
+///     RedisLoggerConfigBuilder::build_with_pubsub(connection, vec!["logging".into()], MessageEncoder)
+/// });
+/// logger.log(&Record::builder().args(format_args!("Started")).build());
+///
+/// assert_eq!(
+///     handle.drain(),
+///     vec![CapturedCommand::Publish {
+///         channel: "logging".to_owned(),
+///         message: b"Started".to_vec(),
+///     }]
+/// );
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct CapturingLogger;
+
+impl CapturingLogger {
+    /// Creates a logger with the given level and the configuration built by `config` around a new `CapturingConnection`,
+    /// and the handle to read back the commands sent on that connection. The logger is not set as the global logger.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<PUBSUB, STREAM, F>(
+        level: LevelFilter,
+        config: F,
+    ) -> (Box<RedisLogger<CapturingConnection, PUBSUB, STREAM>>, CaptureHandle)
+    where
+        PUBSUB: PubSubEncoder + 'static,
+        STREAM: StreamEncoder + 'static,
+        F: FnOnce(CapturingConnection) -> RedisLoggerConfig<CapturingConnection, PUBSUB, STREAM>,
+    {
+        let (connection, handle) = CapturingConnection::new();
+        (RedisLogger::new(level, config(connection)), handle)
+    }
+}
+
+/// A connection that keeps every command instead of sending it, and answers every command with the integer reply `1`.
+/// Created by `CapturingConnection::new` or `CapturingLogger::new`.
+#[derive(Debug)]
+pub struct CapturingConnection {
+    sent: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CapturingConnection {
+    /// Creates a connection and the handle to read back the commands sent on it.
+    pub fn new() -> (Self, CaptureHandle) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        (Self { sent: Arc::clone(&sent) }, CaptureHandle { sent })
+    }
+}
+
+impl ConnectionLike for CapturingConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.sent.lock().unwrap().extend_from_slice(cmd);
+        Ok(Value::Int(1))
+    }
+
+    fn req_packed_commands(&mut self, cmd: &[u8], offset: usize, count: usize) -> RedisResult<Vec<Value>> {
+        self.sent.lock().unwrap().extend_from_slice(cmd);
+        Ok(vec![Value::Int(1); offset + count])
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+
+    fn check_connection(&mut self) -> bool {
+        true
+    }
+
+    fn is_open(&self) -> bool {
+        true
+    }
+}
+
+/// The handle to the commands sent on a `CapturingConnection`.
+#[derive(Debug, Clone)]
+pub struct CaptureHandle {
+    sent: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CaptureHandle {
+    /// Returns the commands sent since the last call, in the order they were sent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sent bytes are not a sequence of RESP arrays of bulk strings, which is how all commands are sent.
+    pub fn drain(&self) -> Vec<CapturedCommand> {
+        let sent = std::mem::take(&mut *self.sent.lock().unwrap());
+        decode_commands(&sent).into_iter().map(CapturedCommand::from_args).collect()
+    }
+}
+
+impl CapturedCommand {
+    fn from_args(args: Vec<Vec<u8>>) -> Self {
+        let string = |arg: &[u8]| String::from_utf8_lossy(arg).into_owned();
+        match args.first().map(Vec::as_slice) {
+            Some(b"PUBLISH") if args.len() == 3 => Self::Publish {
+                channel: string(&args[1]),
+                message: args[2].clone(),
+            },
+            Some(b"XADD") if args.len() >= 3 => {
+                let mut rest = &args[2..];
+                loop {
+                    match rest.first().map(Vec::as_slice) {
+                        Some(b"NOMKSTREAM") => rest = &rest[1..],
+                        Some(b"MAXLEN" | b"MINID") => {
+                            let operator = matches!(rest.get(1).map(Vec::as_slice), Some(b"~" | b"="));
+                            rest = &rest[(if operator { 3 } else { 2 }).min(rest.len())..];
+                        }
+                        Some(b"LIMIT") => rest = &rest[2.min(rest.len())..],
+                        _ => break,
+                    }
+                }
+                let Some((id, fields)) = rest.split_first() else {
+                    return Self::Other(args);
+                };
+                Self::XAdd {
+                    stream: string(&args[1]),
+                    id: string(id),
+                    fields: fields
+                        .chunks(2)
+                        .map(|pair| (string(&pair[0]), pair.get(1).cloned().unwrap_or_default()))
+                        .collect(),
+                }
+            }
+            _ => Self::Other(args),
+        }
+    }
+}
+
+/// Decodes RESP arrays of bulk strings into their arguments.
+pub(crate) fn decode_commands(mut input: &[u8]) -> Vec<Vec<Vec<u8>>> {
+    fn read_number(input: &mut &[u8], prefix: u8) -> usize {
+        assert_eq!(input.first(), Some(&prefix), "unexpected RESP type");
+        let end = input
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .expect("unterminated RESP line");
+        let number = str::from_utf8(&input[1..end]).ok().and_then(|number| number.parse().ok());
+        *input = &input[end + 2..];
+        number.expect("invalid RESP length")
+    }
+
+    let mut commands = Vec::new();
+    while !input.is_empty() {
+        let count = read_number(&mut input, b'*');
+        let args = (0..count)
+            .map(|_| {
+                let len = read_number(&mut input, b'$');
+                let arg = input[..len].to_vec();
+                input = &input[len + 2..];
+                arg
+            })
+            .collect();
+        commands.push(args);
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captured_commands() {
+        let (mut connection, handle) = CapturingConnection::new();
+        let mut pipeline = redis::pipe();
+        pipeline
+            .cmd("PUBLISH")
+            .arg("logging")
+            .arg(&[0xffu8, 0x00][..])
+            .cmd("XADD")
+            .arg("stream")
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(1000)
+            .arg("LIMIT")
+            .arg(10)
+            .arg("*")
+            .arg("level")
+            .arg("INFO")
+            .cmd("LPUSH")
+            .arg("queue")
+            .arg("message");
+        connection.req_packed_commands(&pipeline.get_packed_pipeline(), 0, 3).unwrap();
+
+        assert_eq!(
+            handle.drain(),
+            vec![
+                CapturedCommand::Publish {
+                    channel: "logging".to_owned(),
+                    message: vec![0xff, 0x00],
+                },
+                CapturedCommand::XAdd {
+                    stream: "stream".to_owned(),
+                    id: "*".to_owned(),
+                    fields: vec![("level".to_owned(), b"INFO".to_vec())],
+                },
+                CapturedCommand::Other(vec![b"LPUSH".to_vec(), b"queue".to_vec(), b"message".to_vec()]),
+            ]
+        );
+        assert!(handle.drain().is_empty());
+    }
+}
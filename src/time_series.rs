@@ -0,0 +1,159 @@
+//! # Time Series Module
+//!
+//! This module provides `TimeSeriesTarget`, which adds numeric key-values of records to RedisTimeSeries keys.
+
+use log::kv::{Error, Key, Value, VisitSource};
+use log::Record;
+
+/// `TimeSeriesTarget` adds a numeric key-value of records as samples to a RedisTimeSeries key with `TS.ADD`, so measurements
+/// emitted through `log` (e.g. `log::info!(latency_ms = 42; "Request handled")`) can be charted next to the logs.
+///
+/// Records without the configured key-value, or with a value that is not a number, are ignored by the target.
+/// The sample is timestamped by the Redis server (`*`).
+///
+/// The key is built from a template, where `{target}`, `{level}` and `{key}` are replaced with the record's target,
+/// level and the name of the key-value, e.g. `metrics:{target}:{key}`. The target, the level and all other key-values
+/// of the record are set as `LABELS`, which RedisTimeSeries only applies when the key is created by the first sample.
+/// As every distinct value creates a new label value, log only low-cardinality key-values next to measurements.
+///
+/// Pass it to `RedisLoggerConfig::with_time_series`.
+///
+/// ```rust
+/// # use redis_logger::TimeSeriesTarget;
+/// let target = TimeSeriesTarget::new("latency_ms", "metrics:{target}:{key}");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TimeSeriesTarget {
+    value_key: String,
+    key_template: String,
+}
+
+impl TimeSeriesTarget {
+    /// Creates a target adding the key-value `value_key` to the key built from `key_template`.
+    pub fn new(value_key: impl Into<String>, key_template: impl Into<String>) -> Self {
+        Self {
+            value_key: value_key.into(),
+            key_template: key_template.into(),
+        }
+    }
+
+    pub(crate) fn prefix_key(&mut self, prefix: &str) {
+        self.key_template.insert_str(0, prefix);
+    }
+
+    /// Returns the time series key and the `TS.ADD` command for the record, or `None` if it lacks a numeric value.
+    pub(crate) fn command(&self, record: &Record) -> Option<(String, redis::Cmd)> {
+        let mut sample = Sample {
+            value_key: &self.value_key,
+            value: None,
+            labels: Vec::new(),
+        };
+        // visiting only fails if the visitor returns an error, which ours never does
+        let _ = record.key_values().visit(&mut sample);
+        let value = sample.value?;
+
+        let key = self
+            .key_template
+            .replace("{target}", record.target())
+            .replace("{level}", record.level().as_str())
+            .replace("{key}", &self.value_key);
+        let mut command = redis::cmd("TS.ADD");
+        command
+            .arg(&key)
+            .arg("*")
+            .arg(value)
+            .arg("LABELS")
+            .arg("target")
+            .arg(record.target())
+            .arg("level")
+            .arg(record.level().as_str());
+        for (label, value) in sample.labels {
+            command.arg(label).arg(value);
+        }
+        Some((key, command))
+    }
+}
+
+/// Collects the value of the configured key and all other key-values as labels.
+struct Sample<'a> {
+    value_key: &'a str,
+    value: Option<String>,
+    labels: Vec<(String, String)>,
+}
+
+impl<'kvs> VisitSource<'kvs> for Sample<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        if key.as_str() == self.value_key {
+            // keep integers as they are instead of formatting them as floats
+            self.value = value.to_f64().map(|_| value.to_string());
+        } else {
+            self.labels.push((key.as_str().to_owned(), value.to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    fn args(command: &redis::Cmd) -> Vec<String> {
+        command
+            .args_iter()
+            .map(|arg| match arg {
+                redis::Arg::Simple(arg) => String::from_utf8_lossy(arg).into_owned(),
+                redis::Arg::Cursor => "<cursor>".to_owned(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_time_series_command() {
+        let target = TimeSeriesTarget::new("latency_ms", "metrics:{target}:{key}");
+        let key_values = [
+            ("latency_ms", log::kv::Value::from(42)),
+            ("route", log::kv::Value::from("/users")),
+        ];
+        let record = Record::builder()
+            .level(Level::Info)
+            .args(format_args!("Request handled"))
+            .target("api")
+            .key_values(&key_values)
+            .build();
+
+        let (key, command) = target.command(&record).unwrap();
+        assert_eq!(key, "metrics:api:latency_ms");
+        assert_eq!(
+            args(&command),
+            vec![
+                "TS.ADD",
+                "metrics:api:latency_ms",
+                "*",
+                "42",
+                "LABELS",
+                "target",
+                "api",
+                "level",
+                "INFO",
+                "route",
+                "/users"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_time_series_ignores_records_without_number() {
+        let target = TimeSeriesTarget::new("latency_ms", "metrics:{key}");
+        let record = Record::builder().args(format_args!("No measurement")).build();
+        assert!(target.command(&record).is_none());
+
+        let key_values = [("latency_ms", log::kv::Value::from("fast"))];
+        let record = Record::builder()
+            .args(format_args!("Not a number"))
+            .key_values(&key_values)
+            .build();
+        assert!(target.command(&record).is_none());
+    }
+}
@@ -0,0 +1,61 @@
+//! Fixtures shared by the integration tests.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use log::Record;
+use redis::{ConnectionLike, RedisError, RedisResult, Value};
+use redis_logger::PubSubEncoder;
+
+/// A connection that fails while `up` is not set and collects all commands written to it otherwise.
+pub struct FakeConnection {
+    pub up: Arc<AtomicBool>,
+    pub written: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Default for FakeConnection {
+    /// Creates a connection that is up.
+    fn default() -> Self {
+        Self {
+            up: Arc::new(AtomicBool::new(true)),
+            written: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl ConnectionLike for FakeConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.req_packed_commands(cmd, 0, 1).map(|_| Value::Nil)
+    }
+
+    fn req_packed_commands(&mut self, cmd: &[u8], _offset: usize, count: usize) -> RedisResult<Vec<Value>> {
+        if !self.up.load(Ordering::SeqCst) {
+            return Err(RedisError::from(std::io::Error::from(std::io::ErrorKind::ConnectionRefused)));
+        }
+        self.written.lock().unwrap().extend_from_slice(cmd);
+        Ok(vec![Value::Int(1); count])
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+
+    fn check_connection(&mut self) -> bool {
+        self.up.load(Ordering::SeqCst)
+    }
+
+    fn is_open(&self) -> bool {
+        true
+    }
+}
+
+/// Encodes only the message of a record.
+pub struct MessageEncoder;
+
+impl PubSubEncoder for MessageEncoder {
+    fn encode(&self, record: &Record) -> Vec<u8> {
+        record.args().to_string().into_bytes()
+    }
+}
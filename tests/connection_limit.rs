@@ -0,0 +1,38 @@
+use std::sync::{Arc, Mutex};
+
+use common::{FakeConnection, MessageEncoder};
+use log::LevelFilter;
+use redis_logger::{live_connections, set_max_live_connections, RedisLogger, RedisLoggerConfigBuilder, RedisLoggerError};
+
+mod common;
+
+#[test]
+fn test_max_live_connections() {
+    set_max_live_connections(Some(2));
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let new_logger = || {
+        let captured = Arc::clone(&warnings);
+        let config =
+            RedisLoggerConfigBuilder::build_with_pubsub(FakeConnection::default(), vec!["logging".into()], MessageEncoder)
+                .with_error_handler(move |error| {
+                    if let RedisLoggerError::TooManyConnections { live, max } = error {
+                        captured.lock().unwrap().push((*live, *max));
+                    }
+                });
+        RedisLogger::new(LevelFilter::Info, config)
+    };
+
+    let first = new_logger();
+    let second = new_logger();
+    assert_eq!(live_connections(), 2);
+    assert!(warnings.lock().unwrap().is_empty());
+
+    let third = new_logger();
+    assert_eq!(*warnings.lock().unwrap(), vec![(3, 2)]);
+
+    drop((first, third));
+    assert_eq!(live_connections(), 1);
+    let _fourth = new_logger();
+    assert_eq!(warnings.lock().unwrap().len(), 1);
+    drop(second);
+}
@@ -0,0 +1,26 @@
+use std::sync::{Arc, OnceLock};
+
+use common::{FakeConnection, MessageEncoder};
+use log::LevelFilter;
+use redis_logger::{DummyStreamEncoder, RedisLogger, RedisLoggerConfigBuilder};
+
+mod common;
+
+static LOGGER: OnceLock<Box<RedisLogger<FakeConnection, MessageEncoder, DummyStreamEncoder>>> = OnceLock::new();
+
+#[test]
+fn test_init_static() {
+    let connection = FakeConnection::default();
+    let written = Arc::clone(&connection.written);
+    let config = RedisLoggerConfigBuilder::build_with_pubsub(connection, vec!["logging".into()], MessageEncoder);
+    let logger = LOGGER.get_or_init(|| RedisLogger::new(LevelFilter::Info, config));
+    logger.init_static(LevelFilter::Warn).unwrap();
+    assert!(logger.init_static(LevelFilter::Warn).is_err());
+
+    log::info!("Below the level");
+    log::warn!("Logged through the static logger");
+
+    let written = String::from_utf8_lossy(&written.lock().unwrap()).into_owned();
+    assert!(!written.contains("Below the level"));
+    assert!(written.contains("Logged through the static logger"));
+}
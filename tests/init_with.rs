@@ -0,0 +1,46 @@
+use std::sync::{Arc, Mutex};
+
+use common::{FakeConnection, MessageEncoder};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use redis_logger::{RedisLogger, RedisLoggerConfigBuilder};
+
+mod common;
+
+/// A logger that collects the messages of all warnings it receives.
+struct WarningsLogger {
+    messages: Arc<Mutex<Vec<String>>>,
+}
+
+impl Log for WarningsLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        self.messages.lock().unwrap().push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn test_init_with() {
+    let connection = FakeConnection::default();
+    let written = Arc::clone(&connection.written);
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let warnings = WarningsLogger {
+        messages: Arc::clone(&messages),
+    };
+    let config = RedisLoggerConfigBuilder::build_with_pubsub(connection, vec!["logging".into()], MessageEncoder);
+    RedisLogger::init_with(LevelFilter::Info, config, vec![Box::new(warnings)]).unwrap();
+
+    log::debug!("Below the level");
+    log::info!("Only to Redis");
+    log::warn!("To both loggers");
+
+    let written = String::from_utf8_lossy(&written.lock().unwrap()).into_owned();
+    assert!(!written.contains("Below the level"));
+    assert!(written.contains("Only to Redis"));
+    assert!(written.contains("To both loggers"));
+    assert_eq!(*messages.lock().unwrap(), vec!["To both loggers".to_owned()]);
+}
@@ -0,0 +1,75 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{Level, LevelFilter, Log, Record};
+use redis::{
+    streams::{StreamInfoGroupsReply, StreamInfoStreamReply},
+    Commands,
+};
+use redis_logger::{RedisLogger, RedisLoggerConfigBuilder, StreamEncoder};
+
+struct MessageEncoder;
+
+impl StreamEncoder for MessageEncoder {
+    fn encode(&self, record: &Record) -> Vec<(String, Vec<u8>)> {
+        vec![("message".to_owned(), record.args().to_string().into_bytes())]
+    }
+}
+
+/// Returns a client for the Redis server at `REDIS_URL`, or `None` to skip the test if it is not set, e.g. in CI
+/// running `cargo test --all-features`.
+fn client() -> Option<redis::Client> {
+    let url = std::env::var("REDIS_URL").ok()?;
+    Some(redis::Client::open(url).unwrap())
+}
+
+/// Returns a stream name no other test run uses.
+fn unique_stream(name: &str) -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    format!("redis_logger_test:{name}:{nanos}")
+}
+
+fn log_message(logger: &impl Log) {
+    logger.log(&Record::builder().level(Level::Info).args(format_args!("Provisioned")).build());
+}
+
+#[test]
+fn test_provision_then_log() {
+    let Some(client) = client() else {
+        return;
+    };
+    let stream = unique_stream("provisioned");
+    let config =
+        RedisLoggerConfigBuilder::build_with_streams(client.get_connection().unwrap(), vec![stream.clone()], MessageEncoder)
+            .with_stream_maxlen(1000)
+            .with_no_mkstream(true);
+    config.provision_streams(Some("consumers")).unwrap();
+    // provisioning again keeps the existing stream and group
+    config.provision_streams(Some("consumers")).unwrap();
+    let logger = RedisLogger::new(LevelFilter::Info, config);
+    log_message(&*logger);
+
+    let mut connection = client.get_connection().unwrap();
+    let info: StreamInfoStreamReply = connection.xinfo_stream(&stream).unwrap();
+    let groups: StreamInfoGroupsReply = connection.xinfo_groups(&stream).unwrap();
+    connection.del::<_, ()>(&stream).unwrap();
+    assert_eq!(info.length, 1);
+    assert_eq!(info.groups, 1);
+    assert_eq!(groups.groups[0].name, "consumers");
+    assert_eq!(groups.groups[0].pending, 0);
+}
+
+#[test]
+fn test_no_mkstream_without_provisioning() {
+    let Some(client) = client() else {
+        return;
+    };
+    let stream = unique_stream("unprovisioned");
+    let config =
+        RedisLoggerConfigBuilder::build_with_streams(client.get_connection().unwrap(), vec![stream.clone()], MessageEncoder)
+            .with_no_mkstream(true);
+    let logger = RedisLogger::new(LevelFilter::Info, config);
+    log_message(&*logger);
+
+    let exists: bool = client.get_connection().unwrap().exists(&stream).unwrap();
+    assert!(!exists);
+}
@@ -0,0 +1,44 @@
+#![cfg(all(unix, feature = "signal"))]
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{FakeConnection, MessageEncoder};
+use log::LevelFilter;
+use redis_logger::{install_flush_signal_handler, RedisLogger, RedisLoggerConfigBuilder};
+
+mod common;
+
+#[test]
+fn test_flush_on_signal() {
+    let up = Arc::new(AtomicBool::new(false));
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let connection = FakeConnection {
+        up: Arc::clone(&up),
+        written: Arc::clone(&written),
+    };
+    let config = RedisLoggerConfigBuilder::build_with_pubsub(connection, vec!["logging".into()], MessageEncoder)
+        .with_retry_buffer(10)
+        .with_error_handler(|_| {});
+    RedisLogger::init(LevelFilter::Info, config).unwrap();
+    install_flush_signal_handler(signal_hook::consts::SIGUSR1).unwrap();
+    assert!(install_flush_signal_handler(signal_hook::consts::SIGUSR1).is_err());
+
+    log::info!("Written during the outage");
+    assert!(written.lock().unwrap().is_empty());
+
+    up.store(true, Ordering::SeqCst);
+    signal_hook::low_level::raise(signal_hook::consts::SIGUSR1).unwrap();
+
+    let start = Instant::now();
+    while !String::from_utf8_lossy(&written.lock().unwrap()).contains("Written during the outage") {
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for the flush");
+        thread::sleep(Duration::from_millis(1));
+    }
+}